@@ -0,0 +1,137 @@
+use std::{env, fs};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::commands::install::install;
+use crate::util::build::fns::{create_build_root, fetch_sources, parse_build_script, recipe_to_package, run_build_script};
+use crate::util::database::fns::init_database;
+use crate::util::build::structs::Recipe;
+use crate::util::macros::get_root;
+use crate::util::packaging::fns::{create_package, validate_package};
+
+/// Print an error, leave the build tree around for debugging and exit
+fn abort_build(message: String, build_dir: &Path) -> ! {
+    eprintln!("ERR> {}", message);
+    eprintln!("ERR> The build tree was kept at {}. Aborting...", build_dir.display());
+
+    std::process::exit(1);
+}
+
+pub fn build(args: Vec<String>) {
+    if args.len() < 3 {
+        eprintln!("Please provide a recipe directory to build. (Check bulge --help for usage)");
+
+        std::process::exit(1);
+    }
+
+    let recipe_dir = PathBuf::from(&args[2]);
+
+    let out_dir = match args.iter().position(|a| a == "-o" || a == "--output") {
+        Some(i) if i + 1 < args.len() => PathBuf::from(&args[i + 1]),
+        _ => PathBuf::from("."),
+    };
+
+    let recipe: Result<Recipe, serde_json::Error> = File::open(recipe_dir.join("metadata.json"))
+        .map_err(serde_json::Error::io)
+        .and_then(serde_json::from_reader);
+
+    let recipe = match recipe {
+        Ok(recipe) => recipe,
+        Err(e) => {
+            eprintln!("ERR> Failed to read {}/metadata.json: {}", recipe_dir.display(), e);
+
+            std::process::exit(1);
+        }
+    };
+
+    let script = fs::read_to_string(recipe_dir.join("build"))
+        .map_err(|e| e.to_string())
+        .and_then(|s| parse_build_script(&s));
+
+    let script = match script {
+        Ok(script) => script,
+        Err(e) => {
+            eprintln!("ERR> Failed to read {}/build: {}", recipe_dir.display(), e);
+
+            std::process::exit(1);
+        }
+    };
+
+    let package = recipe_to_package(&recipe);
+
+    if let Err(e) = validate_package(&package) {
+        eprintln!("ERR> {}/metadata.json is not a valid recipe: {}", recipe_dir.display(), e);
+
+        std::process::exit(1);
+    }
+
+    sudo::escalate_if_needed().expect("Failed to escalate to root.");
+
+    println!("==> Building {} v{}-{}...", &package.name, &package.version, &package.epoch);
+
+    // Everything happens inside a scratch root so the build can only see its declared dependencies
+    let build_dir = PathBuf::from(format!("{}/tmp/bulge/build/{}", get_root(), &package.name));
+    let root = build_dir.join("root");
+
+    // Paths handed to the build script are relative to the scratch root
+    let temp = PathBuf::from("/build/temp");
+    let destdir = PathBuf::from("/build/pkg");
+
+    if build_dir.exists() {
+        fs::remove_dir_all(&build_dir).expect("Failed to clear old build tree!");
+    }
+
+    println!("\n==> Creating build root...");
+
+    create_build_root(&root).unwrap_or_else(|e| abort_build(format!("Failed to create build root: {}", e), &build_dir));
+
+    fs::create_dir_all(root.join("build/temp")).expect("Failed to create build tree!");
+    fs::create_dir_all(root.join("build/pkg")).expect("Failed to create build tree!");
+
+    let host_root = env::var("INSTALL_ROOT");
+    env::set_var("INSTALL_ROOT", &root);
+
+    init_database();
+
+    let mut dependencies: Vec<String> = recipe.depends.clone();
+    dependencies.extend(recipe.build_depends.clone());
+
+    if !dependencies.is_empty() {
+        println!("\n==> Installing build dependencies...");
+
+        let mut install_args: Vec<String> = vec!["bulge".to_string(), "install".to_string(), "--noconfirm".to_string()];
+        install_args.append(&mut dependencies);
+
+        install(install_args);
+    }
+
+    match host_root {
+        Ok(host_root) => env::set_var("INSTALL_ROOT", host_root),
+        Err(_) => env::remove_var("INSTALL_ROOT"),
+    }
+
+    println!("\n==> Fetching sources...");
+
+    let sources: Vec<PathBuf> = fetch_sources(&recipe, &recipe_dir, &root.join("build/sources"))
+        .unwrap_or_else(|e| abort_build(e, &build_dir))
+        .iter()
+        .map(|s| Path::new("/").join(s.strip_prefix(&root).expect("Source escaped the build root!")))
+        .collect();
+
+    println!("\n==> Running build script...");
+
+    run_build_script(&script, &sources, &temp, &destdir, &root)
+        .unwrap_or_else(|e| abort_build(e, &build_dir));
+
+    println!("\n==> Packing {}...", &package.name);
+
+    let archive = create_package(package, &root.join("build/pkg"), &out_dir)
+        .unwrap_or_else(|e| abort_build(format!("Failed to create package: {}", e), &build_dir));
+
+    println!("\n==> Cleaning up...");
+
+    // The scratch root goes with the rest of the build tree
+    fs::remove_dir_all(&build_dir).expect("Failed to delete build tree!");
+
+    println!("\n==> Created {}", archive.display());
+}
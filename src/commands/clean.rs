@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::util::cache::get_cache_path;
+use crate::util::database::fns::get_installed_package;
+use crate::util::lock::{create_lock, lock_exists, remove_lock};
+use crate::util::packaging::fns::read_pkg_from_archive;
+use crate::util::packaging::structs::Package;
+use crate::util::version::compare_versions;
+
+/// Which cached archives get removed
+enum CleanPolicy {
+    /// Keep the newest n versions of every package
+    Keep(usize),
+    /// Remove archives of packages that aren't installed
+    Uninstalled,
+    /// Remove everything
+    All
+}
+
+pub fn clean(args: Vec<String>) {
+    let policy = match args.get(2).map(|a| &a[..]) {
+        None => CleanPolicy::Keep(3),
+        Some("--keep") => match args.get(3).and_then(|n| n.parse().ok()) {
+            Some(n) => CleanPolicy::Keep(n),
+            None => {
+                eprintln!("Please provide how many versions to keep. (Check bulge --help for usage)");
+
+                std::process::exit(1);
+            }
+        },
+        Some("--uninstalled") => CleanPolicy::Uninstalled,
+        Some("--all") => CleanPolicy::All,
+        Some(x) => {
+            eprintln!("bulge: Invalid clean option \"{}\", use {{-h --help}} for valid commands.", x);
+
+            std::process::exit(1);
+        }
+    };
+
+    sudo::escalate_if_needed().expect("Failed to escalate to root.");
+
+    lock_exists();
+
+    create_lock().expect("Failed to create lock file. (Does /var/lock/bulge.lock already exist?)");
+
+    println!("==> Cleaning package cache...");
+
+    let entries = match fs::read_dir(get_cache_path()) {
+        Ok(entries) => entries,
+        Err(_) => {
+            println!("==> Package cache is empty.");
+
+            remove_lock().expect("Failed to remove lock?");
+            std::process::exit(0);
+        }
+    };
+
+    let files: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .collect();
+
+    let archives: Vec<PathBuf> = files.iter()
+        .filter(|p| p.to_string_lossy().ends_with(".tar.xz"))
+        .cloned()
+        .collect();
+
+    let mut removing: Vec<PathBuf> = vec![];
+
+    match policy {
+        // Partial downloads and stray signatures go too
+        CleanPolicy::All => removing = files,
+        CleanPolicy::Uninstalled => {
+            for i in archives {
+                let package = File::open(&i).ok().and_then(read_pkg_from_archive);
+
+                if package.is_none_or(|p| get_installed_package(&p.name).is_err()) {
+                    removing.push(i);
+                }
+            }
+        },
+        CleanPolicy::Keep(n) => {
+            let mut versions: HashMap<String, Vec<(Package, PathBuf)>> = HashMap::new();
+
+            for i in archives {
+                match File::open(&i).ok().and_then(read_pkg_from_archive) {
+                    Some(package) => versions.entry(package.name.clone()).or_default().push((package, i)),
+                    // Anything that isn't a readable package is just taking up space
+                    None => removing.push(i),
+                }
+            }
+
+            for (_, mut packages) in versions {
+                // Newest first, epoch wins over version like in upgrade
+                packages.sort_by(|a, b| compare_versions(&b.0.version, b.0.epoch, &a.0.version, a.0.epoch));
+
+                removing.extend(packages.into_iter().skip(n).map(|p| p.1));
+            }
+        }
+    }
+
+    let mut freed: u64 = 0;
+
+    for i in &removing {
+        println!("=> Removing {}", i.display());
+
+        freed += fs::metadata(i).map(|m| m.len()).unwrap_or(0);
+        fs::remove_file(i).expect("Failed to remove cached package!");
+
+        let signature = format!("{}.sig", i.display());
+
+        if Path::new(&signature).exists() && !removing.contains(&PathBuf::from(&signature)) {
+            fs::remove_file(signature).expect("Failed to remove cached signature!");
+        }
+    }
+
+    println!("\n==> Removed {} files, freeing {:.2} MiB.", removing.len(), freed as f64 / 1048576.0);
+
+    remove_lock().expect("Failed to remove lock?");
+}
@@ -6,10 +6,15 @@ pub fn help() {
     println!("\t\t - List all commands for bulge (this view)");
     println!("\t bulge {{s sync}}");
     println!("\t\t - Synchronizes package databases with remotes");
+    println!("\t bulge {{s sync}} --rollback <repo>");
+    println!("\t\t - Restore the previous database of a repo");
     println!("\t bulge {{u upgrade}}");
     println!("\t\t - Check for (and then install) package updates");
-    println!("\t bulge {{i install}} <package(s)>");
-    println!("\t\t - Install a specified package");
+    println!("\t bulge {{i install}} <package(s)> [--noconfirm] [--offline]");
+    println!("\t\t - Install a specified package, use <repo>/<package> to pick the repo");
+    println!("\t\t - Virtual packages ask which provider to install, --noconfirm uses default_providers");
+    println!("\t\t - --noconfirm accepts the install but declines downgrades, replacements and overwriting files");
+    println!("\t\t - --offline only uses cached databases and packages");
     println!("\t bulge {{li localinstall}} <path(s)>");
     println!("\t\t - Install a package from a local archive");
     println!("\t bulge {{r remove}} <package(s)>");
@@ -17,7 +22,29 @@ pub fn help() {
     //println!("\t bulge info <package>");
     //println!("\t\t - TODO");
     println!("\t bulge search <package>");
-    println!("\t\t - For now this will just look if the package exists in the repo without installing it");
+    println!("\t\t - List every repo that has a package with its version, highest priority first");
     println!("\t bulge list");
     println!("\t\t - List all installed packages with their version and source");
+    println!("\t bulge clean [--keep <n> | --uninstalled | --all]");
+    println!("\t\t - Remove cached packages, keeps the newest 3 versions of each package by default");
+    println!("\t bulge pack --pkg <PKG.json> --root <dir> [-o <dir>]");
+    println!("\t\t - Create a package archive from a staged directory");
+    println!("\t bulge build <recipe-dir> [-o <dir>]");
+    println!("\t\t - Build a package from a recipe in a clean root with only its dependencies installed");
+    println!("\t bulge repo list");
+    println!("\t\t - List configured repos with their url, maintainer, update times and database hash");
+    println!("\t bulge repo-add <repo-dir> <package(s)> [--sign <key>]");
+    println!("\t\t - Add package archives to a repo database, optionally signing it");
+    println!("\t bulge repo-remove <repo-dir> <package(s)> [--sign <key>]");
+    println!("\t\t - Remove packages from a repo database, optionally signing it");
+    println!("\t bulge key add <file> [--repo <repo>]");
+    println!("\t\t - Trust a signing key, optionally only for one repo");
+    println!("\t bulge key list");
+    println!("\t\t - List all signing keys with their fingerprint, repo and trust");
+    println!("\t bulge key remove <fingerprint>");
+    println!("\t\t - Remove a signing key");
+    println!("\t bulge key {{trust untrust}} <fingerprint>");
+    println!("\t\t - Mark a signing key as trusted or untrusted");
+    println!("\t bulge setup");
+    println!("\t\t - Create the default configuration and databases, no default signing keys are published yet (see bulge key add)");
 }
\ No newline at end of file
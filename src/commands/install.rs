@@ -1,27 +1,27 @@
 use std::fs;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::Write;
-
-use isahc::http::StatusCode;
-use isahc::ReadResponseExt;
-
-use crate::util::database::fns::{get_remote_package, search_for_package};
-use crate::util::database::structs::Source;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use crate::util::cache::{check_cached_package, get_cache_path, get_cached_package_path};
+use crate::util::config::fns::{get_config_entry, get_repo_credentials, get_signature_policy, get_sources};
+use crate::util::config::structs::{ConfigEntries, SignaturePolicy};
+use crate::util::database::fns::get_install_reason;
+use crate::util::database::structs::{InstallReason, Source};
+use crate::util::download::fns::{download_bytes, download_from_mirrors};
+use crate::util::hash::sha512_hex;
 use crate::util::lock::{create_lock, lock_exists, remove_lock};
-use crate::util::macros::{continue_prompt, display_installing_packages, get, get_root};
-use crate::util::mirrors::load_mirrors;
+use crate::util::macros::{continue_prompt, display_installing_packages, get_root, is_offline, set_noconfirm, set_offline, transaction_prompt};
+use crate::util::mirrors::get_repo_urls;
 use crate::util::packaging::fns::run_remove;
 use crate::util::packaging::structs::Package;
-use crate::util::transactions::conflict::run_conflict_package_check;
-use crate::util::transactions::dependencies::{run_depend_check, run_depend_resolve};
+use crate::util::signing::fns::verify_package;
 use crate::util::transactions::install::{InstallTransaction, run_install};
-
-#[derive(PartialEq, Eq, Hash, Clone)]
-struct Packages {
-    name: String,
-    repo: String
-}
+use crate::util::transactions::resolver::{Requirement, resolve};
+use crate::util::version::Constraint;
 
 pub fn install(args: Vec<String>) {
     if args.len() < 3 {
@@ -36,211 +36,293 @@ pub fn install(args: Vec<String>) {
 
     create_lock().expect("Failed to create lock file. (Does /var/lock/bulge.lock already exist?)");
 
-    let requested_packages: Vec<String> = args.clone().drain(2..).collect();
-    let mut packages: HashSet<Packages> = HashSet::new();
+    if args.iter().any(|a| a == "--noconfirm") {
+        set_noconfirm(true);
+    }
+
+    if args.iter().any(|a| a == "--offline") {
+        set_offline(true);
+    }
+
+    let requested_packages: Vec<String> = args.clone().drain(2..).filter(|a| !a.starts_with("--")).collect();
+    let mut requested: Vec<Requirement> = vec![];
 
-    println!("==> Resolving packages and dependencies...");
     for i in &requested_packages {
-        let repo = search_for_package(&i);
+        // repo/package forces a repo, otherwise the highest priority repo carrying the package wins
+        let (name, repo) = match i.split_once('/') {
+            Some((repo, name)) => {
+                if !get_sources().iter().any(|s| s.name == repo) {
+                    eprintln!("ERR> Repo {} is not configured or not active! Aborting...", repo);
 
-        if repo.is_err() {
-            eprintln!("ERR> {} was not found! Aborting...", i);
-    
-            remove_lock().expect("Failed to remove lock?");
-    
-            std::process::exit(1);
-        }
+                    remove_lock().expect("Failed to remove lock?");
 
-        let repo_unwrap = repo.unwrap();
+                    std::process::exit(1);
+                }
 
-        let remote_package = get_remote_package(&i, &repo_unwrap);
+                (name, Some(repo.to_string()))
+            },
+            None => (i.as_str(), None),
+        };
 
-        if remote_package.is_err() {
-            eprintln!("WARN> {} was not found!", &i);
+        // Packages can be requested with a version constraint, like build dependencies are
+        let constraint = Constraint::parse(name).unwrap_or_else(|e| {
+            eprintln!("ERR> {}! Aborting...", e);
 
-            continue;
-        }
+            remove_lock().expect("Failed to remove lock?");
 
-        packages.insert(Packages {
-            name: i.clone(),
-            repo: repo_unwrap.clone()
+            std::process::exit(1);
         });
 
-        let remote_package = remote_package.unwrap();
+        requested.push(Requirement {
+            constraint,
+            required_by: None,
+            repo
+        });
+    }
 
-        if remote_package.depends.is_empty() {
-            // Let's not check for depends as there is none
-            continue;
-        }
+    install_requirements(requested, vec![]);
+}
 
-        let mut dependencies: HashSet<String> = HashSet::new();
+/// Resolve, download and install a set of requested packages, the caller has to hold the lock.
+///
+/// Installed packages in `replacing` are removed in favour of the package named next to them without asking again.
+pub fn install_requirements(requested: Vec<Requirement>, replacing: Vec<(String, String)>) {
+    println!("==> Resolving packages and dependencies...");
+    let plan = resolve(requested, replacing.clone()).unwrap_or_else(|e| {
+        eprintln!("ERR> {}! Aborting...", e);
 
-        run_depend_resolve(
-            get_remote_package(&i, &repo_unwrap).expect("Failed to get remote package."),
-            &mut dependencies
-        );
+        remove_lock().expect("Failed to remove lock?");
 
-        let checked_deps = run_depend_check(dependencies);
+        std::process::exit(1);
+    });
 
-        for x in checked_deps.iter() {
-            if !x.1 {
-                let repo = search_for_package(&x.0);
+    if plan.install.is_empty() {
+        eprintln!("ERR> No packages in queue! Aborting...");
 
-                if repo.is_err() {
-                    eprintln!("ERR> {} was not found! Aborting...", i);
+        remove_lock().expect("Failed to remove lock?");
 
-                    remove_lock().expect("Failed to remove lock?");
+        std::process::exit(1);
+    }
 
-                    std::process::exit(1);
-                }
+    if is_offline() {
+        // Fail before anything is touched instead of partway through the downloads
+        let mut missing: Vec<String> = plan.install.iter()
+            .filter(|(p, _)| !check_cached_package(p))
+            .map(|(p, _)| format!("{}-{}-{}.tar.xz", &p.name, &p.version, &p.epoch))
+            .collect();
+
+        if !missing.is_empty() {
+            missing.sort();
+
+            eprintln!("ERR> The following packages are not in the cache:");
 
-                packages.insert(Packages {
-                    name: x.0.clone(),
-                    repo: repo.unwrap()
-                });
+            for i in missing {
+                eprintln!("\t{}", i);
             }
+
+            eprintln!("ERR> Cannot install offline! Aborting...");
+
+            remove_lock().expect("Failed to remove lock?");
+            std::process::exit(1);
         }
     }
 
-    if packages.is_empty() {
-        eprintln!("ERR> No packages in queue! Aborting...");
+    for (old, new) in plan.remove.iter().filter(|r| !replacing.contains(r)) {
+        println!("\n==> {} can be replaced with {}", old, new);
 
-        remove_lock().expect("Failed to remove lock?");
+        if !continue_prompt() {
+            println!("ERR> Package conflicts detected. Aborting...");
 
-        std::process::exit(1);
+            remove_lock().expect("Failed to remove lock?");
+            std::process::exit(1);
+        }
     }
 
-    println!("==> Checking for already installed packages...");
-    // TODO: Check for already installed packages and collect them into a hashset to display
-    // let mut installed_packages: HashSet<Package> = HashSet::new();
-
-    println!("==> Looking for package conflicts...");
-    let mut conflict = false;
+    println!("==> Generating install queue...");
+    // Already sorted so every package comes after its dependencies
+    let queue: Vec<(Package, String)> = plan.install.clone();
 
-    let mut possible_replace = false;
-    let mut replace: HashMap<String, String> = HashMap::new();
+    println!("\nPackages to install in order [{}]: {}\n", queue.len(), display_installing_packages(&queue));
 
-    for i in packages.clone() {
-        let conflict_pkg = run_conflict_package_check(&i.name);
+    if !(transaction_prompt()) {
+        println!("Abandoning install!");
 
-        if conflict_pkg.is_conflict {
-            conflict = true;
-            println!("ERR> {} conflicts with:", &i.name);
+        remove_lock().expect("Failed to remove lock?");
+        std::process::exit(1);
+    }
 
-            for x in conflict_pkg.packages {
-                println!("\t{} {}-{}", x.name, x.version, x.epoch);
+    println!("\n==> Downloading packages...");
 
-                if get_remote_package(&i.name, &i.repo).unwrap().provides.contains(&x.name) {
-                    possible_replace = true;
-                    replace.insert(i.name.clone(), x.name);
+    let parallel_downloads: usize = get_config_entry(ConfigEntries::ParallelDownloads, None, None)
+        .expect("Failed to get config parallel_downloads.")
+        .parse()
+        .expect("parallel_downloads must be a number.");
+
+    fs::create_dir_all(get_cache_path()).expect("Failed to create package cache!");
+
+    // Replacements take over the install reason of what they replace, so read it before anything is removed
+    let reasons: HashMap<String, InstallReason> = queue.iter().map(|(package, _)| {
+        let replaced: Vec<InstallReason> = plan.remove.iter()
+            .filter(|(_, new)| new == &package.name)
+            .map(|(old, _)| get_install_reason(old))
+            .collect();
+
+        let reason = if (replaced.is_empty() && plan.explicit.contains(&package.name)) || replaced.contains(&InstallReason::Explicit) {
+            InstallReason::Explicit
+        } else {
+            InstallReason::Dependency
+        };
+
+        (package.name.clone(), reason)
+    }).collect();
+
+    let jobs: Mutex<Vec<(Package, String)>> = Mutex::new(queue.clone());
+    let results: Mutex<Vec<Result<InstallTransaction, String>>> = Mutex::new(vec![]);
+    let abort = AtomicBool::new(false);
+
+    thread::scope(|scope| {
+        for _ in 0..parallel_downloads.max(1) {
+            scope.spawn(|| {
+                // Stop picking up new downloads as soon as anything fails
+                while !abort.load(Ordering::Relaxed) {
+                    let job = jobs.lock().expect("Download queue poisoned!").pop();
+
+                    let (package, repo) = match job {
+                        Some(job) => job,
+                        None => break,
+                    };
+
+                    let reason = reasons[&package.name];
+                    let result = download_package(package, repo, reason);
+
+                    if result.is_err() {
+                        abort.store(true, Ordering::Relaxed);
+                    }
+
+                    results.lock().expect("Download results poisoned!").push(result);
                 }
+            });
+        }
+    });
+
+    // Downloads finish in any order, they're put back in install order below
+    let mut downloaded: HashMap<String, InstallTransaction> = HashMap::new();
+    let mut failed = false;
+
+    for result in results.into_inner().expect("Download results poisoned!") {
+        match result {
+            Ok(transaction) => {
+                downloaded.insert(transaction.package.name.clone(), transaction);
+            },
+            Err(e) => {
+                eprintln!("ERR> {}", e);
+                failed = true;
             }
         }
     }
 
-    if conflict && !possible_replace {
-        println!("ERR> Package conflicts detected. Aborting...");
+    if failed {
+        eprintln!("ERR> Failed to download packages! Aborting...");
 
         remove_lock().expect("Failed to remove lock?");
         std::process::exit(1);
     }
 
-    if possible_replace {
-        for (i, x) in replace {
-            println!("\n==> {} can be replaced with {}", x, i);
+    // println!("\n==> Checking for file conflicts...");
+    // TODO: Split extraction from install for this?
 
-            if continue_prompt() {
-                println!("=> Removing {}...", &x);
-                run_remove(&x);
-            } else {
-                println!("ERR> Package conflicts detected. Aborting...");
+    if !plan.remove.is_empty() {
+        println!("\n==> Removing replaced packages...");
 
-                remove_lock().expect("Failed to remove lock?");
-                std::process::exit(1);
-            }
+        for (old, _) in &plan.remove {
+            println!("=> Removing {}...", old);
+            run_remove(old);
         }
-
-        println!();
     }
 
-    println!("==> Generating install queue...");
-    let mut queue: HashMap<Package, String> = HashMap::new();
-    for i in packages.clone() {
-        queue.insert(
-            get_remote_package(&i.name, &i.repo).expect("Failed to get remote package."),
-            i.repo.clone()
-        );
-    }
+    println!("\n==> Installing packages...");
 
-    println!("\nPackages to install [{}]: {}\n", queue.len(), display_installing_packages(queue.clone()));
+    for (package, _) in &queue {
+        let transaction = downloaded.remove(&package.name).expect("Downloaded package missing from queue!");
 
-    if !(continue_prompt()) {
-        println!("Abandoning install!");
+        let file = File::open(get_cached_package_path(&transaction.package))
+            .expect("Failed to open cached package!");
 
-        remove_lock().expect("Failed to remove lock?");
-        std::process::exit(1);
+        println!("=> Installing {} v{}-{}...", &transaction.package.name, &transaction.package.version, &transaction.package.epoch);
+
+        run_install(transaction, file);
     }
 
-    println!("\n==> Downloading packages...");
+    println!("\n==> Cleaning up...");
 
-    let mut filequeue: HashMap<InstallTransaction, File> = HashMap::new();
+    for i in queue {
+        fs::remove_dir_all(format!("{}/tmp/bulge/{}", get_root(), &i.0.name))
+            .expect("Failed to delete temp path!");
+    }
 
-    for i in queue.clone() {
-        println!("=> Downloading {} v{}-{}...", &i.0.name, &i.0.version, &i.0.epoch);
+    println!("\n==> Complete!");
 
-        for x in load_mirrors() {
-            let url = format!("{}/{}-{}-{}.tar.xz", x.replace("$repo", &*i.1),
-                              &i.0.name, &i.0.version, &i.0.epoch);
+    remove_lock().expect("Failed to remove lock?");
+}
 
-            let mut downloaded_package = get(&url).expect("Failed to get package.");
+/// Get a verified copy of a package into the cache along with its detached signature.
+///
+/// A cached archive matching the repo's sha512sum is reused, otherwise the first mirror with a verified copy is used.
+/// Either way the archive is checked against the repo's signature policy, so nothing is touched if one fails.
+fn download_package(package: Package, repo: String, reason: InstallReason) -> Result<InstallTransaction, String> {
+    let path = get_cached_package_path(&package);
+    let sig_path = PathBuf::from(format!("{}.sig", path.display()));
 
-            if downloaded_package.status() != StatusCode::OK  {
-                println!("Failed to get {}. Status: {}", &url, downloaded_package.status());
-                continue;
-            }
+    if check_cached_package(&package) {
+        println!("=> Using cached {} v{}-{}...", &package.name, &package.version, &package.epoch);
 
-            File::create(format!("{}/tmp/{}-{}-{}.tar.xz", get_root(),
-                                 &i.0.name, &i.0.version, &i.0.epoch))
-                .expect("Failed to create temporary file!")
-                .write_all(downloaded_package.bytes().expect("Failed to get bytes.").as_slice())
-                .expect("Failed to write to temporary file!");
+        check_signature(&package, &repo, &path, fs::read(&sig_path).ok())?;
 
-            let file = File::open(format!("{}/tmp/{}-{}-{}.tar.xz", get_root(),
-                                              &i.0.name, &i.0.version, &i.0.epoch))
-                .expect("Failed to open temporary file!");
+        return Ok(InstallTransaction {
+            package,
+            source: Source { name: repo, url: None },
+            reason
+        });
+    }
 
-            filequeue.insert(InstallTransaction {
-                package: i.0.clone(),
-                source: Source { name: i.1, url: Some(url) }
-            }, file);
+    println!("=> Downloading {} v{}-{}...", &package.name, &package.version, &package.epoch);
 
-            break;
-        }
-    }
+    let urls: Vec<String> = get_repo_urls(&repo)?.iter()
+        .map(|x| format!("{}/{}-{}-{}.tar.xz", x, &package.name, &package.version, &package.epoch))
+        .collect();
 
-    // println!("\n==> Checking for file conflicts...");
-    // TODO: Split extraction from install for this?
+    let credentials = get_repo_credentials(&repo)?;
 
-    println!("\n==> Installing packages...");
+    // Make sure the mirror gave us the archive the repo database describes
+    let url = download_from_mirrors(&urls, &path, credentials.as_ref(), |p| {
+        File::open(p).and_then(sha512_hex).is_ok_and(|h| h == package.sha512sum)
+    }).map_err(|e| format!("Could not get a verified copy of {}: {}", &package.name, e))?;
 
-    for i in filequeue {
-        println!("=> Installing {} v{}-{}...", &i.0.package.name, &i.0.package.version, &i.0.package.epoch);
+    // Grab the detached signature if the repo publishes one
+    let mut signature: Option<Vec<u8>> = None;
 
-        run_install(i.0, i.1);
+    if get_signature_policy(&repo) != SignaturePolicy::Never {
+        signature = download_bytes(&format!("{}.sig", &url), credentials.as_ref()).ok();
     }
 
-    println!("\n==> Cleaning up...");
+    // Keep the signature with the archive so a cached copy can still be checked later
+    match &signature {
+        Some(signature) => fs::write(&sig_path, signature),
+        None if sig_path.exists() => fs::remove_file(&sig_path),
+        None => Ok(()),
+    }.map_err(|e| format!("Failed to cache signature for {}: {}", &package.name, e))?;
 
-    for i in queue {
-        fs::remove_dir_all(format!("{}/tmp/bulge/{}", get_root(), &i.0.name))
-            .expect("Failed to delete temp path!");
+    check_signature(&package, &repo, &path, signature)?;
 
-        fs::remove_file(format!("{}/tmp/{}-{}-{}.tar.xz", get_root(),
-                                &i.0.name, &i.0.version, &i.0.epoch))
-            .expect("Failed to remove temporary file!");
-    }
-
-    println!("\n==> Complete!");
+    Ok(InstallTransaction {
+        package,
+        source: Source { name: repo, url: Some(url) },
+        reason
+    })
+}
 
-    remove_lock().expect("Failed to remove lock?");
-}
\ No newline at end of file
+/// Check a downloaded package against its repo's signature policy before anything gets installed.
+fn check_signature(package: &Package, repo: &String, path: &Path, signature: Option<Vec<u8>>) -> Result<(), String> {
+    verify_package(repo, path, signature.as_deref())
+        .map_err(|e| format!("{} failed signature verification: {}", &package.name, e))
+}
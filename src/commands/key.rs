@@ -0,0 +1,141 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::util::signing::fns::{find_key, get_fingerprint, load_keys, save_key};
+use crate::util::signing::structs::TrustedKey;
+
+pub fn key(args: Vec<String>) {
+    if args.len() < 3 {
+        eprintln!("Please provide a key command. (Check bulge --help for usage)");
+
+        std::process::exit(1);
+    }
+
+    match &args[2].to_lowercase()[..] {
+        "add" => add(args),
+        "list" => list(),
+        "remove" => remove(args),
+        "trust" => set_trusted(args, true),
+        "untrust" => set_trusted(args, false),
+        _ => {
+            eprintln!("bulge: Invalid key command \"{}\", use {{-h --help}} for valid commands.", args[2]);
+
+            std::process::exit(1);
+        }
+    }
+}
+
+fn add(args: Vec<String>) {
+    if args.len() < 4 {
+        eprintln!("Please provide a key file to add. (Check bulge --help for usage)");
+
+        std::process::exit(1);
+    }
+
+    // Keys can optionally be scoped to a single repo
+    let repo = match args.iter().position(|a| a == "--repo") {
+        Some(i) if i + 1 < args.len() => Some(args[i + 1].clone()),
+        Some(_) => {
+            eprintln!("Please provide a repo for --repo. (Check bulge --help for usage)");
+
+            std::process::exit(1);
+        },
+        None => None,
+    };
+
+    sudo::escalate_if_needed().expect("Failed to escalate to root.");
+
+    let contents = fs::read_to_string(&args[3]).unwrap_or_else(|_| {
+        eprintln!("ERR> Failed to read {}!", &args[3]);
+
+        std::process::exit(1);
+    });
+
+    // Accept either a key file exported by bulge or a bare hex encoded public key
+    let mut key: TrustedKey = serde_json::from_str(&contents).unwrap_or(TrustedKey {
+        public_key: contents.trim().to_string(),
+        repo: None,
+        trusted: true,
+        path: PathBuf::new()
+    });
+
+    if repo.is_some() {
+        key.repo = repo;
+    }
+
+    key.trusted = true;
+
+    let fingerprint = match hex::decode(key.public_key.trim()) {
+        Ok(raw_key) if raw_key.len() == 32 => get_fingerprint(&key.public_key).expect("Failed to get fingerprint!"),
+        _ => {
+            eprintln!("ERR> {} is not a valid ed25519 public key!", &args[3]);
+
+            std::process::exit(1);
+        }
+    };
+
+    if find_key(&fingerprint).is_some() {
+        eprintln!("ERR> Key {} is already in the keyring!", fingerprint);
+
+        std::process::exit(1);
+    }
+
+    save_key(&key).expect("Failed to save key!");
+
+    println!("==> Added key {}", fingerprint);
+}
+
+fn list() {
+    for i in load_keys() {
+        let fingerprint = get_fingerprint(&i.public_key).unwrap_or_else(|_| "invalid".to_string());
+        let repo = i.repo.unwrap_or_else(|| "all repos".to_string());
+        let trusted = if i.trusted { "trusted" } else { "untrusted" };
+
+        println!("{} {} {}", fingerprint, repo, trusted);
+    }
+}
+
+fn remove(args: Vec<String>) {
+    if args.len() < 4 {
+        eprintln!("Please provide a key fingerprint to remove. (Check bulge --help for usage)");
+
+        std::process::exit(1);
+    }
+
+    sudo::escalate_if_needed().expect("Failed to escalate to root.");
+
+    let key = get_key_or_exit(&args[3]);
+
+    fs::remove_file(&key.path).expect("Failed to remove key file!");
+
+    println!("==> Removed key {}", get_fingerprint(&key.public_key).expect("Failed to get fingerprint!"));
+}
+
+fn set_trusted(args: Vec<String>, trusted: bool) {
+    if args.len() < 4 {
+        eprintln!("Please provide a key fingerprint. (Check bulge --help for usage)");
+
+        std::process::exit(1);
+    }
+
+    sudo::escalate_if_needed().expect("Failed to escalate to root.");
+
+    let mut key = get_key_or_exit(&args[3]);
+    key.trusted = trusted;
+
+    save_key(&key).expect("Failed to save key!");
+
+    println!("==> {} key {}", if trusted { "Trusted" } else { "Untrusted" },
+             get_fingerprint(&key.public_key).expect("Failed to get fingerprint!"));
+}
+
+fn get_key_or_exit(fingerprint: &str) -> TrustedKey {
+    match find_key(fingerprint) {
+        Some(key) => key,
+        None => {
+            eprintln!("ERR> No single key matches {}!", fingerprint);
+
+            std::process::exit(1);
+        }
+    }
+}
@@ -3,10 +3,11 @@ use std::fs;
 use std::fs::File;
 use std::path::Path;
 
-use crate::util::database::structs::Source;
+use crate::util::database::structs::{InstallReason, Source};
 use crate::util::lock::{create_lock, lock_exists, remove_lock};
 use crate::util::macros::{continue_prompt, get_root};
-use crate::util::packaging::fns::{check_if_package, decode_pkg_file, decompress_xz};
+use crate::util::packaging::fns::read_pkg_from_archive;
+use crate::util::signing::fns::verify_package;
 use crate::util::transactions::install::{InstallTransaction, run_install};
 
 pub fn local_install(args: Vec<String>) {
@@ -27,19 +28,28 @@ pub fn local_install(args: Vec<String>) {
     for i in &packages {
         // Check if i is a valid path and assume it's a file we want to install if it is
         if Path::new(i).exists() {
-            if !check_if_package(decompress_xz(fs::File::open(i).expect("Failed to read package!"))) {
-                println!("WARN> {} is not a valid package!", i);
-            }
+            // Nothing is read out of the archive until it's verified
+            let signature = fs::read(format!("{}.sig", i)).ok();
+
+            if let Err(e) = verify_package(&"local".to_string(), Path::new(i), signature.as_deref()) {
+                eprintln!("ERR> {} failed signature verification: {} Aborting...", i, e);
 
-            let mut package_tar = decompress_xz(fs::File::open(i).expect("Failed to read package!"));
-            package_tar.unpack(format!("{}/tmp/bulge/{}", get_root(), &i)).unwrap();
+                remove_lock().expect("Failed to remove lock?");
+                std::process::exit(1);
+            }
 
-            let package = decode_pkg_file(fs::File::open(format!("{}/tmp/bulge/{}/PKG", get_root(), &i))
-                .expect("Failed to open PKG file!"));
+            let package = match File::open(i).ok().and_then(read_pkg_from_archive) {
+                Some(package) => package,
+                None => {
+                    println!("WARN> {} is not a valid package!", i);
+                    continue;
+                }
+            };
 
             package_queue.insert(InstallTransaction {
                 package: package,
-                source: Source{ name: "local".to_string(), url: None }
+                source: Source{ name: "local".to_string(), url: None },
+                reason: InstallReason::Explicit
             }, fs::File::open(i).expect("Failed to read package!"));
         } else {
             println!("WARN> {} is not a valid package!", i);
@@ -85,11 +95,6 @@ pub fn local_install(args: Vec<String>) {
 
     println!("\n==> Cleaning up...");
 
-    for i in &packages {
-        fs::remove_dir_all(format!("{}/tmp/bulge/{}", get_root(), &i))
-            .expect("Failed to delete temp path!");
-    }
-
     for i in clean_up_list {
         fs::remove_dir_all(format!("{}/tmp/bulge/{}", get_root(), &i))
             .expect("Failed to delete temp path!");
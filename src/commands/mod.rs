@@ -6,4 +6,11 @@ pub mod localinstall;
 pub mod remove;
 pub mod list;
 pub mod groupinstall;
-pub mod search;
\ No newline at end of file
+pub mod search;
+pub mod setup;
+pub mod setup_files;
+pub mod key;
+pub mod clean;
+pub mod repo;
+pub mod pack;
+pub mod build;
@@ -0,0 +1,71 @@
+use std::fs::File;
+use std::path::Path;
+
+use crate::util::hash::sha512_hex;
+use crate::util::packaging::fns::{create_package, validate_package};
+use crate::util::packaging::structs::Package;
+
+/// Returns the value following any of the given flags
+fn get_flag(args: &[String], flags: &[&str]) -> Option<String> {
+    let position = args.iter().position(|a| flags.contains(&&a[..]))?;
+
+    args.get(position + 1).cloned()
+}
+
+pub fn pack(args: Vec<String>) {
+    let pkg_path = get_flag(&args, &["--pkg"]);
+    let root = get_flag(&args, &["--root"]);
+    let out_dir = get_flag(&args, &["-o", "--output"]).unwrap_or_else(|| ".".to_string());
+
+    if pkg_path.is_none() || root.is_none() {
+        eprintln!("Please provide a PKG file and a root to package. (Check bulge --help for usage)");
+
+        std::process::exit(1);
+    }
+
+    let (pkg_path, root) = (pkg_path.unwrap(), root.unwrap());
+
+    let package: Result<Package, serde_json::Error> = File::open(&pkg_path)
+        .map_err(serde_json::Error::io)
+        .and_then(serde_json::from_reader);
+
+    let package = match package {
+        Ok(package) => package,
+        Err(e) => {
+            eprintln!("ERR> Failed to read {}: {}", pkg_path, e);
+
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = validate_package(&package) {
+        eprintln!("ERR> {} is not a valid PKG file: {}", pkg_path, e);
+
+        std::process::exit(1);
+    }
+
+    if !Path::new(&root).is_dir() {
+        eprintln!("ERR> {} is not a directory!", root);
+
+        std::process::exit(1);
+    }
+
+    println!("==> Packing {} v{}-{}...", &package.name, &package.version, &package.epoch);
+
+    match create_package(package, Path::new(&root), Path::new(&out_dir)) {
+        Ok(path) => {
+            println!("==> Created {}", path.display());
+
+            // The archive can't carry its own hash, so show it for anyone publishing the package by hand
+            match File::open(&path).and_then(sha512_hex) {
+                Ok(hash) => println!("=> SHA-512: {}", hash),
+                Err(e) => println!("WARN> Failed to hash {}: {}", path.display(), e),
+            }
+        },
+        Err(e) => {
+            eprintln!("ERR> Failed to create package: {}", e);
+
+            std::process::exit(1);
+        }
+    }
+}
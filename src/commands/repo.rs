@@ -0,0 +1,237 @@
+use std::fs;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::util::config::fns::get_all_sources;
+use crate::util::database::fns::{add_package_to_repo, get_cached_repo, get_repo_metadata, init_repo_database, remove_package_from_repo};
+use crate::util::download::fns::redact_url;
+use crate::util::hash::sha512_hex;
+use crate::util::mirrors::get_repo_urls;
+use crate::util::packaging::fns::{read_pkg_from_archive, validate_package};
+use crate::util::packaging::structs::Package;
+use crate::util::signing::fns::sign_message;
+
+pub fn repo(args: Vec<String>) {
+    if args.len() < 3 {
+        eprintln!("Please provide a repo command. (Check bulge --help for usage)");
+
+        std::process::exit(1);
+    }
+
+    match &args[2].to_lowercase()[..] {
+        "list" => list(),
+        _ => {
+            eprintln!("bulge: Invalid repo command \"{}\", use {{-h --help}} for valid commands.", args[2]);
+
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Show every configured repo with what was stored about it during the last sync
+fn list() {
+    for (source, active) in get_all_sources() {
+        let url = source.url.clone()
+            .or_else(|| get_repo_urls(&source.name).ok().and_then(|u| u.into_iter().next()))
+            .map_or("none".to_string(), |u| redact_url(&u));
+
+        let metadata = get_repo_metadata(&source.name).ok();
+        let cached = get_cached_repo(&source.name).ok();
+
+        println!("{} ({})", source.name, if active { "active" } else { "inactive" });
+        println!("\turl: {}", url);
+
+        match &metadata {
+            Some(metadata) => {
+                println!("\tmaintainer: {} <{}>", metadata.maintainer.name, metadata.maintainer.email);
+                println!("\tremote updated: {}", metadata.last_updated);
+            },
+            None => {
+                println!("\tmaintainer: unknown");
+                println!("\tremote updated: unknown");
+            }
+        }
+
+        match &cached {
+            Some(cached) => {
+                println!("\tlocal updated: {}", cached.last_updated);
+                println!("\thash: {}", cached.repo_hash);
+            },
+            None => {
+                println!("\tlocal updated: never synced");
+                println!("\thash: none");
+            }
+        }
+    }
+}
+
+/// Pull `--sign <key>` out of the arguments, returning the key path if it was given
+fn take_signing_key(args: &mut Vec<String>) -> Option<PathBuf> {
+    let position = args.iter().position(|a| a == "--sign")?;
+
+    if position + 1 >= args.len() {
+        eprintln!("Please provide a private key for --sign. (Check bulge --help for usage)");
+
+        std::process::exit(1);
+    }
+
+    let key = args.remove(position + 1);
+    args.remove(position);
+
+    Some(PathBuf::from(key))
+}
+
+/// Regenerate database.hash and, when a key is given, database.sig for a repo directory
+fn finish_repo_database(repo_dir: &Path, signing_key: Option<PathBuf>) {
+    let database_path = repo_dir.join("database.db");
+
+    let hash = sha512_hex(File::open(&database_path).expect("Failed to open repo database!"))
+        .expect("Failed to hash repo database!");
+
+    fs::write(repo_dir.join("database.hash"), &hash).expect("Failed to write database.hash!");
+
+    println!("=> Updated database.hash");
+
+    if let Some(key) = signing_key {
+        let signature = sign_message(&key, &fs::read(&database_path).expect("Failed to read repo database!"))
+            .unwrap_or_else(|e| {
+                eprintln!("ERR> {}", e);
+
+                std::process::exit(1);
+            });
+
+        fs::write(repo_dir.join("database.sig"), signature).expect("Failed to write database.sig!");
+
+        println!("=> Updated database.sig");
+    } else if repo_dir.join("database.sig").exists() {
+        // An old signature no longer matches the database, don't leave it around to fail verification
+        fs::remove_file(repo_dir.join("database.sig")).expect("Failed to remove database.sig!");
+
+        println!("WARN> Removed the outdated database.sig, use --sign to sign the repo database.");
+    }
+}
+
+pub fn repo_add(mut args: Vec<String>) {
+    let signing_key = take_signing_key(&mut args);
+
+    if args.len() < 4 {
+        eprintln!("Please provide a repo directory and packages to add. (Check bulge --help for usage)");
+
+        std::process::exit(1);
+    }
+
+    let repo_dir = PathBuf::from(&args[2]);
+    let database_path = repo_dir.join("database.db").to_string_lossy().to_string();
+
+    // Check everything before the repo is touched, so a bad archive can't leave the database and its hash out of sync
+    if let Some(key) = &signing_key {
+        if let Err(e) = sign_message(key, &[]) {
+            eprintln!("ERR> {} Aborting...", e);
+
+            std::process::exit(1);
+        }
+    }
+
+    let mut packages: Vec<(&String, Package)> = vec![];
+
+    for i in &args[3..] {
+        let package = File::open(i).ok().and_then(read_pkg_from_archive);
+
+        let mut package = match package {
+            Some(package) => package,
+            None => {
+                eprintln!("ERR> {} is not a valid package! Aborting...", i);
+
+                std::process::exit(1);
+            }
+        };
+
+        // The name ends up in a file name in the repo, so it can't be allowed to point anywhere else
+        if let Err(e) = validate_package(&package) {
+            eprintln!("ERR> {} has an invalid PKG file: {} Aborting...", i, e);
+
+            std::process::exit(1);
+        }
+
+        package.sha512sum = match File::open(i).and_then(sha512_hex) {
+            Ok(hash) => hash,
+            Err(e) => {
+                eprintln!("ERR> Failed to hash {}: {} Aborting...", i, e);
+
+                std::process::exit(1);
+            }
+        };
+
+        packages.push((i, package));
+    }
+
+    fs::create_dir_all(&repo_dir).expect("Failed to create repo directory!");
+    init_repo_database(&database_path).expect("Failed to create repo database!");
+
+    println!("==> Adding packages to {}...", repo_dir.display());
+
+    for (i, package) in packages {
+        // Packages are downloaded as <name>-<version>-<epoch>.tar.xz from the repo root
+        let archive_name = format!("{}-{}-{}.tar.xz", &package.name, &package.version, &package.epoch);
+        let archive_path = repo_dir.join(&archive_name);
+
+        if fs::canonicalize(i).ok() != fs::canonicalize(&archive_path).ok() {
+            fs::copy(i, &archive_path).expect("Failed to copy package into repo!");
+        }
+
+        // Bring a detached signature along with the archive
+        let signature = format!("{}.sig", i);
+
+        if Path::new(&signature).exists() {
+            fs::copy(&signature, repo_dir.join(format!("{}.sig", &archive_name)))
+                .expect("Failed to copy package signature into repo!");
+        }
+
+        add_package_to_repo(&database_path, &package).expect("Failed to add package to repo database!");
+
+        println!("=> Added {} v{}-{}", &package.name, &package.version, &package.epoch);
+    }
+
+    finish_repo_database(&repo_dir, signing_key);
+
+    println!("\n==> Complete!");
+}
+
+pub fn repo_remove(mut args: Vec<String>) {
+    let signing_key = take_signing_key(&mut args);
+
+    if args.len() < 4 {
+        eprintln!("Please provide a repo directory and packages to remove. (Check bulge --help for usage)");
+
+        std::process::exit(1);
+    }
+
+    let repo_dir = PathBuf::from(&args[2]);
+    let database_path = repo_dir.join("database.db");
+
+    if !database_path.exists() {
+        eprintln!("ERR> {} has no repo database! Aborting...", repo_dir.display());
+
+        std::process::exit(1);
+    }
+
+    let database_path = database_path.to_string_lossy().to_string();
+
+    println!("==> Removing packages from {}...", repo_dir.display());
+
+    for i in &args[3..] {
+        match remove_package_from_repo(&database_path, i) {
+            Ok(true) => println!("=> Removed {}", i),
+            Ok(false) => println!("WARN> Package {} not found.", i),
+            Err(e) => {
+                eprintln!("ERR> Failed to remove {}: {} Aborting...", i, e);
+
+                std::process::exit(1);
+            }
+        }
+    }
+
+    finish_repo_database(&repo_dir, signing_key);
+
+    println!("\n==> Complete!");
+}
@@ -1,4 +1,4 @@
-use crate::util::database::fns::{get_remote_package, search_for_package};
+use crate::util::database::fns::{find_package_repos, get_remote_package};
 
 pub fn search(args: Vec<String>) {
 
@@ -12,19 +12,18 @@ pub fn search(args: Vec<String>) {
 
     println!("==> Searching...");
     for i in &requested_packages {
-        let repo = search_for_package(&i);
+        let repos = find_package_repos(i);
 
-        if repo.is_err() {
-            eprintln!("ERR> {} was not found!", i)
+        if repos.is_empty() {
+            eprintln!("ERR> {} was not found!", i);
+            continue;
         }
-	let repo_unwrap = repo.unwrap();
-	let remote_package = get_remote_package(&i, &repo_unwrap);
-	if remote_package.is_err() {
-            eprintln!("ERR> {} was not found!", &i);
-	    continue;
-	}
-        else {
-            println!("{} exists!", i)
+
+        // Repos are in priority order, so the first one is what install would pick
+        for repo in repos {
+            if let Ok(remote_package) = get_remote_package(i, &repo) {
+                println!("{}/{} {}-{}", repo, remote_package.name, remote_package.version, remote_package.epoch);
+            }
         }
     }
 }
@@ -1,11 +1,14 @@
 use std::fs;
 use std::io::Write;
+use std::path::PathBuf;
 
-use crate::commands::setup_files::static_files::{default_config, default_mirrorlist};
+use crate::commands::setup_files::static_files::{default_config, default_keys, default_mirrorlist};
 use crate::commands::sync::sync;
 use crate::util::database::fns::init_database;
 use crate::util::lock::{create_lock, lock_exists, remove_lock};
 use crate::util::macros::get_root;
+use crate::util::signing::fns::save_key;
+use crate::util::signing::structs::TrustedKey;
 
 pub fn init() {
     sudo::escalate_if_needed().expect("Failed to escalate to root.");
@@ -27,6 +30,9 @@ pub fn init() {
     println!("Creating {}/etc/bulge/databases/cache", get_root());
     fs::create_dir_all(get_root() +"/etc/bulge/databases/cache").expect("Failed to create /etc/bulge/databases/cache");
 
+    println!("Creating {}/etc/bulge/keys", get_root());
+    fs::create_dir_all(get_root() + "/etc/bulge/keys").expect("Failed to create /etc/bulge/keys");
+
     println!();
     println!("We'll now create some default files.");
     println!();
@@ -43,6 +49,25 @@ pub fn init() {
         .write_all(default_mirrorlist().as_ref())
         .expect("Failed to insert default mirror list.");
 
+    let keys = default_keys();
+
+    if keys.is_empty() {
+        // Without a trusted key the repos fall back to optional signatures, so say so instead of implying they're covered
+        println!("WARN> No default signing keys have been published for yiffOS yet, signatures are optional until one is trusted.");
+        println!("WARN> Use bulge key add <file> to trust a repo's key.");
+    } else {
+        println!("Installing default signing keys for yiffOS.");
+    }
+
+    for (public_key, repo) in keys {
+        save_key(&TrustedKey {
+            public_key: public_key.to_string(),
+            repo: repo.map(|r| r.to_string()),
+            trusted: true,
+            path: PathBuf::new()
+        }).expect("Failed to install default signing key.");
+    }
+
     println!("Creating default databases.");
     init_database();
 
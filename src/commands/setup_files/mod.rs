@@ -1 +1 @@
-pub mod static_files;
\ No newline at end of file
+pub mod static_files;
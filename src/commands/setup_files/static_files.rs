@@ -4,6 +4,16 @@ pub fn default_config() -> &'static str {
         "toolchain": "knot",
         "colour": true,
         "progressbar": true,
+        "parallel_downloads": 5,
+        "local_signatures": "optional",
+        "default_providers": {},
+        "network": {
+            "proxy": null,
+            "no_proxy": [],
+            "connect_timeout": 15,
+            "read_timeout": 30,
+            "user_agent": "bulge/$version"
+        },
         "repos": [
             {
                 "name": "core",
@@ -20,7 +30,11 @@ pub fn default_config() -> &'static str {
             {
                 "name": "external",
                 "active": false,
-                "url": "https://www.example.com"
+                "url": "https://www.example.com",
+                "mirrors": [
+                    "https://mirror.example.com/$arch/$toolchain"
+                ],
+                "signatures": "optional"
             }
         ]
     }"#
@@ -30,4 +44,12 @@ pub fn default_mirrorlist() -> &'static str {
     r#"# yiffOS default mirror list
 
 https://repo.yiffos.gay/$repo/$arch/$toolchain"#
-}
\ No newline at end of file
+}
+
+/// Default signing keys for yiffOS as (hex encoded ed25519 public key, repo) pairs.
+///
+/// Keys scoped to no repo are trusted for every repo.
+pub fn default_keys() -> Vec<(&'static str, Option<&'static str>)> {
+    // The yiffOS repo signing keys get added here once they are published, along with dropping the note in bulge setup's help
+    vec![]
+}
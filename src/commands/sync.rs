@@ -1,19 +1,26 @@
+use std::fs;
 use std::fs::File;
-use std::io::{copy, Read};
-
-use isahc::http::StatusCode;
-use ring::digest::{Context, SHA512};
-
-use hex::ToHex;
-
-use crate::util::config::fns::get_sources;
-use crate::util::database::fns::update_cached_repos;
+use std::path::{Path, PathBuf};
+
+use crate::util::config::fns::{get_repo_credentials, get_signature_policy, get_sources};
+use crate::util::config::structs::{RepoCredentials, SignaturePolicy};
+use crate::util::database::fns::{get_cached_repo_hash, update_cached_repos, update_repo_metadata};
+use crate::util::database::structs::RepoMetadata;
+use crate::util::download::fns::{download_bytes, download_from_mirrors, redact_url};
+use crate::util::hash::sha512_hex;
 use crate::util::lock::{create_lock, lock_exists, remove_lock};
-use crate::util::macros::{get, get_root};
-use crate::util::mirrors::load_mirrors;
-
-use isahc::prelude::*;
-use isahc::{Body, Response};
+use crate::util::macros::get_root;
+use crate::util::mirrors::get_repo_urls;
+use crate::util::signing::fns::verify_signature;
+
+/// Entry point for the sync command, handles --rollback before falling back to a normal sync
+pub fn sync_command(args: Vec<String>) {
+    if args.len() > 2 && args[2] == "--rollback" {
+        rollback(args);
+    } else {
+        sync();
+    }
+}
 
 pub fn sync() {
     sudo::escalate_if_needed().expect("Failed to escalate to root.");
@@ -24,91 +31,224 @@ pub fn sync() {
 
     println!("=== Synchronizing Repo Databases ===");
 
+    let mut failed: Vec<String> = vec![];
+
     for i in get_sources() {
         println!("=> Updating {}", i.name);
 
-        let mirror_list = load_mirrors();
-
-        for x in mirror_list {
-            let url: String;
+        let credentials = match get_repo_credentials(&i.name) {
+            Ok(credentials) => credentials,
+            Err(e) => {
+                eprintln!("ERR> {}", e);
 
-            if i.url.is_some() {
-                url = format!("{}/database.db", i.url.clone().expect("Failed to extract custom repo url"));
-            } else {
-                url = format!("{}/database.db", x.replace("$repo", &*i.name));
-            }
-
-            let db_response = get(&url);
-
-            if db_response.is_err() {
-                println!("Failed to get {}. Error: {}", &url, db_response.err().unwrap());
+                failed.push(i.name.clone());
                 continue;
             }
+        };
 
-            let mut db_response_unwrap: Response<Body> = db_response.expect("Response errored while bypassing the check");
+        let urls = match get_repo_urls(&i.name) {
+            Ok(urls) => urls,
+            Err(e) => {
+                eprintln!("ERR> {}", e);
 
-            if db_response_unwrap.status() != StatusCode::OK  {
-                println!("Failed to get {}. Status: {}", &url, db_response_unwrap.status());
+                failed.push(i.name.clone());
                 continue;
             }
+        };
+
+        let mut updated = false;
+
+        for base_url in urls {
+            let url = format!("{}/database.db", base_url);
+            let hash_url = format!("{}/database.hash", base_url);
+            let sig_url = format!("{}/database.sig", base_url);
+
+            let hash_string = match download_bytes(&hash_url, credentials.as_ref()) {
+                Ok(hash) => match String::from_utf8(hash) {
+                    Ok(hash) => hash,
+                    Err(_) => {
+                        println!("Failed to get {}. Error: not a valid hash", redact_url(&hash_url));
+                        continue;
+                    }
+                },
+                Err(e) => {
+                    println!("Failed to get {}. Error: {}", redact_url(&hash_url), e);
+                    continue;
+                }
+            };
 
-            let content_bytes = db_response_unwrap.bytes().expect("Failed to get bytes from response");
-            let mut content = content_bytes.as_slice();
-            let mut content_save = content.clone();
-
-            let hash_url: String;
+            // Nothing to download if the repo hasn't changed since the last sync
+            let cached_hash = get_cached_repo_hash(&i.name);
 
-            if i.url.is_some() {
-                hash_url = format!("{}/database.hash", i.url.clone().expect("Failed to extract custom repo url"));
-            } else {
-                hash_url = format!("{}/database.hash", x.replace("$repo", &*i.name));
-            }
+            if cached_hash.is_ok_and(|h| h == hash_string)
+                && Path::new(&format!("{}/etc/bulge/databases/cache/{}.db", get_root(), i.name)).exists() {
+                println!("=> {} is up to date", i.name);
 
-            let hash_response = get(&hash_url);
+                sync_repo_metadata(&i.name, &base_url, credentials.as_ref());
 
-            if hash_response.is_err() {
-                println!("Failed to get {}. Error: {}", &hash_url, hash_response.err().unwrap());
-                continue;
+                updated = true;
+                break;
             }
 
-            let mut hash_response_unwrap: Response<Body> = hash_response.expect("Response errored while bypassing the check");
+            let download_path = PathBuf::from(format!("{}/etc/bulge/databases/cache/{}.db.tmp", get_root(), i.name));
+
+            // Failures are reported by download_from_mirrors, a stale .part gets one retry from scratch
+            let downloaded = download_from_mirrors(std::slice::from_ref(&url), &download_path, credentials.as_ref(), |p| {
+                File::open(p).and_then(sha512_hex).is_ok_and(|h| h == hash_string)
+            });
 
-            if hash_response_unwrap.status() != StatusCode::OK  {
-                println!("Failed to get {}. Status: {}", &hash_url, hash_response_unwrap.status());
+            if downloaded.is_err() {
                 continue;
             }
 
-            let hash = hash_response_unwrap.bytes().expect("Failed to read database bytes");
-            let hash_string = String::from_utf8(hash.clone()).expect("Failed to convert hash to string");
-
-            let mut context = Context::new(&SHA512);
-            let mut buffer = [0; 1024];
-
-            loop {
-                let read = content.read(&mut buffer).expect("Failed to read database.db! Aborting...");
-                if read == 0 {
-                    break;
+            let policy = get_signature_policy(&i.name);
+
+            if policy != SignaturePolicy::Never {
+                let signature: Option<Vec<u8>> = match download_bytes(&sig_url, credentials.as_ref()) {
+                    Ok(signature) => Some(signature),
+                    Err(e) => {
+                        println!("Failed to get {}. Error: {}", redact_url(&sig_url), e);
+                        None
+                    },
+                };
+
+                let content = fs::read(&download_path).expect("Failed to read downloaded database!");
+
+                // Never let an untrusted database replace the cached copy
+                let trusted = match signature {
+                    Some(signature) => {
+                        let valid = verify_signature(&i.name, &content, &signature);
+
+                        if !valid {
+                            println!("!!!> Signature verification failed for {}, trying next mirror. <!!!", redact_url(&sig_url));
+                        }
+
+                        valid
+                    },
+                    None if policy == SignaturePolicy::Required => {
+                        println!("!!!> {} is not signed, trying next mirror. <!!!", redact_url(&url));
+                        false
+                    },
+                    None => {
+                        println!("WARN> {} is not signed.", redact_url(&url));
+                        true
+                    },
+                };
+
+                if !trusted {
+                    fs::remove_file(&download_path).expect("Failed to remove downloaded database!");
+                    continue;
                 }
-                context.update(&buffer[..read]);
-            }
-
-            let generated_hash = context.finish();
-
-            if generated_hash.as_ref().encode_hex::<String>() != hash_string {
-                println!("!!!> Verification failed for {}, trying next mirror. <!!!", hash_url);
-                continue;
             }
 
-            let mut dest = File::create(format!("{}/etc/bulge/databases/cache/{}.db", get_root(), i.name)).expect("Failed to create database file!");
-            copy(&mut content_save, &mut dest).expect("Failed to copy downloaded content");
+            replace_cached_database(&i.name, &download_path).expect("Failed to replace database file!");
 
             update_cached_repos(&i.name, &hash_string);
 
+            sync_repo_metadata(&i.name, &base_url, credentials.as_ref());
+
+            updated = true;
             break;
         }
+
+        if !updated {
+            eprintln!("ERR> Could not get a verified database for {} from any mirror, keeping the cached copy.", i.name);
+
+            failed.push(i.name.clone());
+        }
+    }
+
+    if !failed.is_empty() {
+        eprintln!("ERR> Failed to synchronize {}! Aborting...", failed.join(", "));
+
+        remove_lock().expect("Failed to remove lock?");
+        std::process::exit(1);
     }
 
     println!("=== Synchronization Complete ===");
 
     remove_lock().expect("Failed to remove lock?");
 }
+
+/// Fetch a repo's metadata.json and store it in bulge.db.
+///
+/// The metadata is only informational, so a repo without one still syncs.
+fn sync_repo_metadata(repo: &String, base_url: &String, credentials: Option<&RepoCredentials>) {
+    let url = format!("{}/metadata.json", base_url);
+
+    let metadata: RepoMetadata = match download_bytes(&url, credentials) {
+        Ok(metadata) => match serde_json::from_slice(&metadata) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                println!("WARN> {} is not valid: {}", redact_url(&url), e);
+                return;
+            }
+        },
+        Err(e) => {
+            println!("WARN> Failed to get {}. Error: {}", redact_url(&url), e);
+            return;
+        }
+    };
+
+    update_repo_metadata(repo, &metadata).expect("Failed to insert repo metadata into database!");
+}
+
+/// Restore the previous copy of a repo's cached database
+pub fn rollback(args: Vec<String>) {
+    if args.len() < 4 {
+        eprintln!("Please provide a repo to roll back. (Check bulge --help for usage)");
+
+        std::process::exit(1);
+    }
+
+    sudo::escalate_if_needed().expect("Failed to escalate to root.");
+
+    lock_exists();
+
+    create_lock().expect("Failed to create lock file. (Does /var/lock/bulge.lock already exist?)");
+
+    let repo = &args[3];
+    let old_path = format!("{}/etc/bulge/databases/cache/{}.db.old", get_root(), repo);
+
+    if !Path::new(&old_path).exists() {
+        eprintln!("ERR> There is no previous database for {}! Aborting...", repo);
+
+        remove_lock().expect("Failed to remove lock?");
+        std::process::exit(1);
+    }
+
+    println!("=> Rolling back {}", repo);
+
+    let tmp_path = PathBuf::from(format!("{}/etc/bulge/databases/cache/{}.db.tmp", get_root(), repo));
+    fs::copy(&old_path, &tmp_path).expect("Failed to copy previous database");
+
+    let hash = sha512_hex(File::open(&tmp_path).expect("Failed to open previous database!"))
+        .expect("Failed to hash previous database!");
+
+    // The current database becomes the old copy, so a rollback can itself be rolled back
+    replace_cached_database(repo, &tmp_path).expect("Failed to replace database file!");
+
+    update_cached_repos(repo, &hash);
+
+    println!("=== Rollback Complete ===");
+
+    remove_lock().expect("Failed to remove lock?");
+}
+
+/// Move a new database for a repo into the cache without ever leaving a partial file behind.
+///
+/// The new database has to be on the same filesystem as the cache, the previous copy is kept as `<repo>.db.old`.
+fn replace_cached_database(repo: &String, new_database: &Path) -> std::io::Result<()> {
+    let cache_path = format!("{}/etc/bulge/databases/cache/{}.db", get_root(), repo);
+
+    File::open(new_database)?.sync_all()?;
+
+    if Path::new(&cache_path).exists() {
+        fs::copy(&cache_path, format!("{}.old", cache_path))?;
+    }
+
+    fs::rename(new_database, &cache_path)?;
+
+    // Make sure the rename itself survives a crash
+    File::open(format!("{}/etc/bulge/databases/cache", get_root()))?.sync_all()
+}
@@ -1,7 +1,32 @@
-use version_compare::Version;
-use crate::commands::install::install;
-use crate::util::database::fns::{get_all_installed, get_remote_package};
+use std::cmp::Ordering;
+use crate::commands::install::install_requirements;
+use crate::util::config::fns::get_sources;
+use crate::util::database::fns::{get_all_installed, get_remote_package, get_replaces};
+use crate::util::database::structs::InstalledPackages;
 use crate::util::lock::{create_lock, lock_exists, remove_lock};
+use crate::util::macros::continue_prompt;
+use crate::util::packaging::structs::Package;
+use crate::util::transactions::resolver::Requirement;
+use crate::util::version::{compare_versions, Constraint};
+
+/// Find a repo package that replaces an installed one, returns it with its repo
+fn find_replacement(package: &InstalledPackages, installed: &[InstalledPackages]) -> Option<(Package, String)> {
+    for source in get_sources() {
+        for replacement in get_replaces(&source.name, &package.name) {
+            // get_replaces matches substrings, so check the list properly
+            let replaces = replacement.replaces.split(',')
+                .filter_map(|r| Constraint::parse(r).ok())
+                .any(|r| r.name == package.name && r.satisfied_by(&package.version, package.epoch));
+
+            // Don't offer a swap for something that's already installed
+            if replaces && replacement.name != package.name && !installed.iter().any(|i| i.name == replacement.name) {
+                return Some((replacement, source.name));
+            }
+        }
+    }
+
+    None
+}
 
 pub fn upgrade() {
     sudo::escalate_if_needed().expect("Failed to escalate to root.");
@@ -16,9 +41,11 @@ pub fn upgrade() {
     println!("==> Checking for updates...");
 
     let installed_packages = get_all_installed();
-    let mut updates: Vec<String> = Vec::new();
+    // Updates come from the repo the package was installed from, not whichever repo has the highest priority
+    let mut updates: Vec<(String, String)> = Vec::new();
+    let mut replacements: Vec<(String, String, (Package, String), bool)> = Vec::new();
 
-    for i in installed_packages {
+    for i in &installed_packages {
         let source = i.clone().source;
         let source_name = source.split(",").collect::<Vec<&str>>()[0];
 
@@ -28,24 +55,54 @@ pub fn upgrade() {
 
         let remote_package = get_remote_package(&i.name, &source_name.to_string());
 
-        if remote_package.is_err() {
-            continue;
+        // A higher epoch always forces an upgrade
+        let outdated = remote_package.is_ok_and(|remote_package| {
+            compare_versions(&remote_package.version, remote_package.epoch, &i.version, i.epoch) == Ordering::Greater
+        });
+
+        // The version check still matters if the swap is declined
+        if let Some(replacement) = find_replacement(i, &installed_packages) {
+            replacements.push((i.name.clone(), source_name.to_string(), replacement, outdated));
+        } else if outdated {
+            updates.push((i.name.clone(), source_name.to_string()));
         }
+    }
+
+    let mut requested: Vec<Requirement> = updates.into_iter().map(|(name, source)| Requirement {
+        constraint: Constraint::parse(&name).expect("Installed package has an invalid name?"),
+        required_by: None,
+        repo: Some(source)
+    }).collect();
 
-        let remote_package = remote_package.unwrap();
+    let mut replacing: Vec<(String, String)> = Vec::new();
+
+    for (old, source, (package, repo), outdated) in replacements {
+        println!("\n==> {} has been replaced by {}/{} {}-{}", old, repo, package.name, package.version, package.epoch);
+
+        if !continue_prompt() {
+            println!("=> Keeping {}", old);
+
+            if outdated {
+                requested.push(Requirement {
+                    constraint: Constraint::parse(&old).expect("Installed package has an invalid name?"),
+                    required_by: None,
+                    repo: Some(source)
+                });
+            }
 
-        // Always force upgrade if the epoch is higher
-        if &remote_package.epoch > &i.epoch {
-            updates.push(i.name.clone());
             continue;
         }
 
-        if Version::from(&*remote_package.version) > Version::from(&*i.version) {
-            updates.push(i.name.clone());
-        }
+        requested.push(Requirement {
+            constraint: Constraint::parse(&package.name).expect("Repo package has an invalid name?"),
+            required_by: None,
+            repo: Some(repo)
+        });
+
+        replacing.push((old, package.name));
     }
 
-    match updates.len() {
+    match requested.len() {
         0 => {
             println!("==> No updates found.");
 
@@ -53,21 +110,15 @@ pub fn upgrade() {
             std::process::exit(0);
         },
         1 => {
-            println!("==> Updating {} package...", updates.len());
+            println!("==> Updating {} package...", requested.len());
         },
         _ => {
-            println!("==> Updating {} packages...", updates.len());
+            println!("==> Updating {} packages...", requested.len());
         }
     }
 
-    // Append padding to updates so install will accept it
-    let mut padding: Vec<String> = vec!["0".to_string(), "1".to_string()];
-    padding.append(&mut updates);
-
-    // Remove the lock as the install will takeover
-    remove_lock().expect("Failed to remove lock?");
-
-    install(padding);
+    // The old packages are removed in the same transaction that installs their replacements
+    install_requirements(requested, replacing);
 
     // remove_lock is done by install
-}
\ No newline at end of file
+}
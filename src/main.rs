@@ -25,8 +25,8 @@ fn main() {
         "--help" => commands::help::help(),
 
         // Sync commands
-        "s" => commands::sync::sync(),
-        "sync" => commands::sync::sync(),
+        "s" => commands::sync::sync_command(args),
+        "sync" => commands::sync::sync_command(args),
 
         // Upgrade commands
         "u" => commands::upgrade::upgrade(),
@@ -50,6 +50,24 @@ fn main() {
         // List commands
         "list" => commands::list::list(),
 
+        // Cache commands
+        "clean" => commands::clean::clean(args),
+
+        // Repo commands
+        "repo" => commands::repo::repo(args),
+        "repo-add" => commands::repo::repo_add(args),
+        "repo-remove" => commands::repo::repo_remove(args),
+
+        // Packaging commands
+        "pack" => commands::pack::pack(args),
+        "build" => commands::build::build(args),
+
+        // Key commands
+        "key" => commands::key::key(args),
+
+        // Setup commands
+        "setup" => commands::setup::init(),
+
         // Specify that command is invalid and show help command
         _ => {
             println!("bulge: Invalid command \"{}\", use {{-h --help}} for valid commands.", command);
@@ -0,0 +1,197 @@
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::util::build::structs::{BuildScript, Recipe};
+use crate::util::cache::get_cache_path;
+use crate::util::config::fns::get_credential_files;
+use crate::util::download::fns::download_file;
+use crate::util::hash::sha512_hex;
+use crate::util::macros::{get_root, vec_to_string};
+use crate::util::packaging::structs::Package;
+
+/// Split a build script into its [prepare], [build] and [install] sections
+pub fn parse_build_script(script: &str) -> Result<BuildScript, String> {
+    let mut build_script = BuildScript::default();
+    let mut section: Option<&mut String> = None;
+
+    for (number, line) in script.lines().enumerate() {
+        match line.trim() {
+            "[prepare]" => section = Some(&mut build_script.prepare),
+            "[build]" => section = Some(&mut build_script.build),
+            "[install]" => section = Some(&mut build_script.install),
+            x if x.starts_with('[') && x.ends_with(']') => {
+                return Err(format!("Unknown section {} on line {}", x, number + 1));
+            },
+            x => match section.as_mut() {
+                Some(section) => {
+                    section.push_str(line);
+                    section.push('\n');
+                },
+                None if x.is_empty() || x.starts_with('#') => {},
+                None => return Err(format!("Line {} is outside of a section", number + 1)),
+            }
+        }
+    }
+
+    Ok(build_script)
+}
+
+/// Fetch every source of a recipe into a directory and check them against the recipe's sha512sums.
+///
+/// Sources are urls or paths relative to the recipe, the returned paths are in the same order as the recipe.
+pub fn fetch_sources(recipe: &Recipe, recipe_dir: &Path, dest: &Path) -> Result<Vec<PathBuf>, String> {
+    if recipe.source.len() != recipe.sha512sums.len() {
+        return Err(format!("{} sources but {} sha512sums", recipe.source.len(), recipe.sha512sums.len()));
+    }
+
+    fs::create_dir_all(dest).map_err(|e| e.to_string())?;
+
+    let mut sources: Vec<PathBuf> = vec![];
+
+    for (source, sha512sum) in recipe.source.iter().zip(&recipe.sha512sums) {
+        let file_name = source.rsplit('/').next().unwrap_or(source).split('?').next().unwrap_or(source);
+        let path = dest.join(file_name);
+
+        println!("=> Fetching {}...", source);
+
+        if source.contains("://") || source.starts_with('/') {
+            download_file(source, &path, None).map_err(|e| format!("Failed to get {}: {}", source, e))?;
+        } else {
+            fs::copy(recipe_dir.join(source), &path).map_err(|e| format!("Failed to copy {}: {}", source, e))?;
+        }
+
+        let generated_hash = File::open(&path).and_then(sha512_hex).map_err(|e| e.to_string())?;
+
+        if &generated_hash != sha512sum {
+            return Err(format!("Verification failed for {}", source));
+        }
+
+        sources.push(path);
+    }
+
+    Ok(sources)
+}
+
+/// Create a scratch root for a clean build, bulge's config, keys and synced databases are copied from the host.
+///
+/// The package cache is shared with the host through a symlink so dependencies aren't downloaded twice.
+pub fn create_build_root(root: &Path) -> io::Result<()> {
+    let host_root = get_root();
+
+    for i in ["etc/bulge/databases/cache", "etc/bulge/keys", "tmp", "var/cache/bulge", "proc", "dev", "build"] {
+        fs::create_dir_all(root.join(i))?;
+    }
+
+    for i in ["etc/bulge/config.json", "etc/bulge/mirrors"] {
+        fs::copy(format!("{}/{}", host_root, i), root.join(i))?;
+    }
+
+    for i in ["etc/bulge/databases/cache", "etc/bulge/keys"] {
+        for entry in fs::read_dir(format!("{}/{}", host_root, i))? {
+            let entry = entry?;
+
+            if entry.file_type()?.is_file() {
+                fs::copy(entry.path(), root.join(i).join(entry.file_name()))?;
+            }
+        }
+    }
+
+    // Private repos need their credentials to fetch build dependencies, fs::copy keeps the root-only mode.
+    // Only files that resolve under /etc/bulge are copied, the same rule get_repo_credentials enforces.
+    for i in get_credential_files() {
+        let source = match fs::canonicalize(format!("{}{}", host_root, i)) {
+            Ok(source) if source.is_file() => source,
+            _ => continue,
+        };
+
+        let dest = match source.strip_prefix(format!("{}/", host_root)) {
+            Ok(relative) if relative.starts_with("etc/bulge") => root.join(relative),
+            _ => continue,
+        };
+
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::copy(source, dest)?;
+    }
+
+    fs::create_dir_all(get_cache_path())?;
+    symlink(get_cache_path(), root.join("var/cache/bulge/pkg"))
+}
+
+/// Run the sections of a build script in order with a single `sh -e` inside a build root.
+///
+/// The script runs in its own mount and pid namespace chrooted into `root`, starting in `$temp`. Sections share the
+/// shell, so the working directory and variables carry over from one section to the next.
+/// `$srcN`, `$temp` and `$DESTDIR` are passed to the script as environment variables and are paths inside the root.
+pub fn run_build_script(script: &BuildScript, sources: &[PathBuf], temp: &Path, destdir: &Path, root: &Path) -> Result<(), String> {
+    // The running section is written into the root so a failure can be blamed on it
+    let marker = Path::new("/build/.section");
+    let mut shell_script = String::from("cd \"$temp\"\n");
+
+    for (name, section) in [("prepare", &script.prepare), ("build", &script.build), ("install", &script.install)] {
+        if section.trim().is_empty() {
+            continue;
+        }
+
+        shell_script.push_str(&format!("echo \"=> Running {}...\"\necho {} > {}\n{}", name, name, marker.display(), section));
+    }
+
+    let mut command = Command::new("unshare");
+
+    // proc and dev only exist inside the namespace, so they're gone again once the section finishes
+    command.args(["--mount", "--pid", "--fork", "--kill-child"])
+        .arg(format!("--mount-proc={}", root.join("proc").display()))
+        .args(["sh", "-e", "-c", "mount -t devtmpfs devtmpfs \"$1/dev\" && exec chroot \"$1\" /bin/sh -e -c \"$2\"", "sh"])
+        .arg(root)
+        .arg(shell_script)
+        // Nothing from the host environment leaks into the build
+        .env_clear()
+        .env("PATH", "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin")
+        .env("HOME", "/build")
+        .env("temp", temp)
+        .env("DESTDIR", destdir);
+
+    for (i, source) in sources.iter().enumerate() {
+        command.env(format!("src{}", i), source);
+    }
+
+    let status = command.status().map_err(|e| format!("Failed to run build script: {}", e))?;
+
+    if !status.success() {
+        let name = fs::read_to_string(root.join(marker.strip_prefix("/").expect("Marker is absolute")))
+            .map(|n| n.trim().to_string())
+            .unwrap_or_else(|_| "build script".to_string());
+
+        return Err(format!("{} failed with {}", name, status));
+    }
+
+    Ok(())
+}
+
+/// Turn a recipe into the PKG of the package it builds
+pub fn recipe_to_package(recipe: &Recipe) -> Package {
+    // Packages always provide themselves
+    let mut provides = vec![recipe.name.clone()];
+    provides.extend(recipe.provides.iter().filter(|p| **p != recipe.name).cloned());
+
+    Package {
+        name: recipe.name.clone(),
+        version: recipe.version.clone(),
+        epoch: recipe.epoch,
+        description: recipe.summary.clone(),
+        groups: vec_to_string(recipe.category.clone()),
+        url: recipe.homepage.clone(),
+        license: recipe.license.clone(),
+        depends: vec_to_string(recipe.depends.clone()),
+        optional_depends: vec_to_string(recipe.optional_depends.clone()),
+        provides: vec_to_string(provides),
+        conflicts: vec_to_string(recipe.conflicts.clone()),
+        replaces: vec_to_string(recipe.replaces.clone()),
+        sha512sum: String::new()
+    }
+}
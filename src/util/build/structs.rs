@@ -0,0 +1,47 @@
+use serde::Deserialize;
+
+/// Struct form of a recipe's metadata.json.
+///
+/// See the design document for the layout of a recipe, fields bulge doesn't use like maintainers are ignored.
+#[derive(Deserialize)]
+pub struct Recipe {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub epoch: i32,
+    pub summary: String,
+    #[serde(default)]
+    pub homepage: String,
+    #[serde(default)]
+    pub license: String,
+    /// Turned into the package's groups.
+    #[serde(default)]
+    pub category: Vec<String>,
+    /// Urls or paths relative to the recipe, available to the build script as `$srcN`.
+    #[serde(default)]
+    pub source: Vec<String>,
+    /// SHA-512 of every source, in the same order as `source`.
+    #[serde(default)]
+    pub sha512sums: Vec<String>,
+    #[serde(default)]
+    pub depends: Vec<String>,
+    /// Only installed into the build root, never recorded in the package.
+    #[serde(default)]
+    pub build_depends: Vec<String>,
+    #[serde(default)]
+    pub optional_depends: Vec<String>,
+    #[serde(default)]
+    pub provides: Vec<String>,
+    #[serde(default)]
+    pub conflicts: Vec<String>,
+    #[serde(default)]
+    pub replaces: Vec<String>
+}
+
+/// The sections of a recipe's build script.
+#[derive(Default)]
+pub struct BuildScript {
+    pub prepare: String,
+    pub build: String,
+    pub install: String
+}
@@ -0,0 +1,23 @@
+use std::fs::File;
+use std::path::PathBuf;
+
+use crate::util::hash::sha512_hex;
+use crate::util::macros::get_root;
+use crate::util::packaging::structs::Package;
+
+/// Returns the path of the persistent package cache
+pub fn get_cache_path() -> String {
+    get_root() + "/var/cache/bulge/pkg"
+}
+
+/// Returns where a package's archive is kept in the cache
+pub fn get_cached_package_path(package: &Package) -> PathBuf {
+    PathBuf::from(format!("{}/{}-{}-{}.tar.xz", get_cache_path(), &package.name, &package.version, &package.epoch))
+}
+
+/// Returns true if the cache holds an archive matching the package's sha512sum
+pub fn check_cached_package(package: &Package) -> bool {
+    File::open(get_cached_package_path(package))
+        .and_then(sha512_hex)
+        .is_ok_and(|h| h == package.sha512sum)
+}
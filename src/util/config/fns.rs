@@ -1,29 +1,37 @@
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::prelude::*;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
 use crate::util::database::structs::Source;
-use crate::util::config::structs::{ConfigEntries, ConfigError, Config, RepoEntries, RepoNode};
+use crate::util::config::structs::{ConfigEntries, ConfigError, Config, NetworkConfig, RepoCredentials, RepoEntries, RepoNode, SignaturePolicy};
 use crate::util::macros::get_root;
+use crate::util::signing::fns::has_trusted_key;
 
 
-/// Returns a string of the requested config entry, optionally returns a config entry within a repo.
-///
-/// See [ConfigEntries] and [RepoEntries].
-pub fn get_config_entry(entry: ConfigEntries, repo: Option<String>, repo_entry: Option<RepoEntries>) -> Result<String, ConfigError> {
-    // Load config file
+/// Load and parse the config file.
+fn load_config() -> Config {
     let mut x = String::new();
-    
+
     File::open(get_root() + "/etc/bulge/config.json")
         .expect("Failed to open config file, is another process accessing it?")
         .read_to_string(&mut x)
         .expect("Failed to convert file to string");
 
-    let config: Config = serde_json::from_str(&x).expect("Failed to serialize data");
+    serde_json::from_str(&x).expect("Failed to serialize data")
+}
+
+/// Returns a string of the requested config entry, optionally returns a config entry within a repo.
+///
+/// See [ConfigEntries] and [RepoEntries].
+pub fn get_config_entry(entry: ConfigEntries, repo: Option<String>, repo_entry: Option<RepoEntries>) -> Result<String, ConfigError> {
+    let config = load_config();
 
     match entry {
         ConfigEntries::Architecture => Ok(config.architecture),
         ConfigEntries::Toolchain => Ok(config.toolchain),
         ConfigEntries::Colour => Ok(config.colour.to_string()),
         ConfigEntries::Progressbar => Ok(config.progressbar.to_string()),
+        ConfigEntries::ParallelDownloads => Ok(config.parallel_downloads.to_string()),
         ConfigEntries::Repos => {
             // Check if a repo and a repo config entry were supplied
             if repo.is_none() && repo_entry.is_none() {
@@ -54,26 +62,19 @@ pub fn get_config_entry(entry: ConfigEntries, repo: Option<String>, repo_entry:
 ///
 /// Currently only used for [get_sources].
 fn get_repo_vec() -> Vec<RepoNode> {
-        // Load config file
-        let mut x = String::new();
-    
-        File::open(get_root() + "/etc/bulge/config.json")
-            .expect("Failed to open config file, is another process accessing it?")
-            .read_to_string(&mut x)
-            .expect("Failed to convert file to string");
-    
-        let config: Config = serde_json::from_str(&x).expect("Failed to serialize data");
-
-        return config.repos
+    load_config().repos
 }
 
-/// Return sources in config.
+/// Return active sources in config, highest priority first.
 ///
 /// See [Source].
 pub fn get_sources() -> Vec<Source> {
     let mut sources: Vec<Source> = vec![];
 
-    let repo_config_entry: Vec<RepoNode> = get_repo_vec();
+    let mut repo_config_entry: Vec<RepoNode> = get_repo_vec();
+
+    // Stable sort, so repos with the same priority stay in config order
+    repo_config_entry.sort_by_key(|r| std::cmp::Reverse(r.priority));
 
     for i in repo_config_entry {
         if i.active == true {
@@ -87,3 +88,101 @@ pub fn get_sources() -> Vec<Source> {
     return sources;
 }
 
+/// Return every repo in config along with whether it is active.
+pub fn get_all_sources() -> Vec<(Source, bool)> {
+    get_repo_vec().into_iter()
+        .map(|i| (Source { name: i.name, url: i.url }, i.active))
+        .collect()
+}
+
+/// Returns the signature policy of a repo.
+///
+/// The "local" source used by localinstall has its own policy, unknown repos are always [SignaturePolicy::Required].
+/// A repo without a policy is [SignaturePolicy::Required] as soon as a trusted key covers it, so a mirror can't just leave the signature out.
+pub fn get_signature_policy(repo: &String) -> SignaturePolicy {
+    let config = load_config();
+
+    if repo == "local" {
+        return config.local_signatures;
+    }
+
+    config.repos.into_iter()
+        .find(|r| &r.name == repo)
+        .map_or(SignaturePolicy::Required, |r| r.signatures.unwrap_or_else(|| {
+            if has_trusted_key(repo) { SignaturePolicy::Required } else { SignaturePolicy::Optional }
+        }))
+}
+
+/// Returns the mirror list configured for a single repo, if it has one.
+pub fn get_repo_mirrors(repo: &String) -> Option<Vec<String>> {
+    get_repo_vec().into_iter()
+        .find(|r| &r.name == repo)
+        .and_then(|r| r.mirrors)
+}
+
+/// Returns the configured default provider for a virtual package, if there is one.
+pub fn get_default_provider(package: &String) -> Option<String> {
+    load_config().default_providers.remove(package)
+}
+
+/// Returns the network section of the config, defaults are used when it's missing.
+pub fn get_network_config() -> NetworkConfig {
+    load_config().network
+}
+
+/// Returns every credentials file referenced by a repo, as configured.
+pub fn get_credential_files() -> Vec<String> {
+    get_repo_vec().into_iter()
+        .filter_map(|r| match r.credentials {
+            Some(RepoCredentials::File { file }) => Some(file),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the credentials configured for a repo, with credential files already read.
+///
+/// Credential files have to live under /etc/bulge, be owned by root and not be readable by anyone else.
+/// A repo with credentials needs its own url or mirrors, they're never sent to the global mirror list.
+pub fn get_repo_credentials(repo: &String) -> Result<Option<RepoCredentials>, String> {
+    let node = match get_repo_vec().into_iter().find(|r| &r.name == repo) {
+        Some(node) => node,
+        None => return Ok(None),
+    };
+
+    if node.credentials.is_some() && node.url.is_none() && node.mirrors.as_ref().is_none_or(|m| m.is_empty()) {
+        return Err(format!("Repo {} has credentials but no url or mirrors of its own!", repo));
+    }
+
+    let credentials = node.credentials;
+
+    let file = match credentials {
+        Some(RepoCredentials::File { file }) => file,
+        credentials => return Ok(credentials),
+    };
+
+    // The path is inside the root like every other bulge file, so it still resolves from a build root
+    let path = fs::canonicalize(get_root() + &file)
+        .map_err(|e| format!("Failed to open credentials file {}: {}", file, e))?;
+
+    if !path.starts_with(Path::new(&(get_root() + "/etc/bulge"))) {
+        return Err(format!("Credentials file {} is not under /etc/bulge!", file));
+    }
+
+    let metadata = fs::metadata(&path)
+        .map_err(|e| format!("Failed to open credentials file {}: {}", file, e))?;
+
+    if metadata.uid() != 0 || metadata.mode() & 0o077 != 0 {
+        return Err(format!("Credentials file {} must be owned by root and only readable by root!", file));
+    }
+
+    let x = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read credentials file {}: {}", file, e))?;
+
+    // Don't echo serde's error, it can quote the file's contents
+    match serde_json::from_str(&x) {
+        Ok(RepoCredentials::File { .. }) => Err(format!("Credentials file {} can't reference another file!", file)),
+        Ok(credentials) => Ok(Some(credentials)),
+        Err(_) => Err(format!("Credentials file {} is not valid!", file)),
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fmt::{self};
 use serde::Deserialize;
 
@@ -22,6 +23,7 @@ pub enum ConfigEntries {
     Toolchain,
     Colour,
     Progressbar,
+    ParallelDownloads,
     Repos
 }
 
@@ -32,6 +34,18 @@ pub enum RepoEntries {
     Url
 }
 
+/// How strictly signatures are enforced for a repo.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SignaturePolicy {
+    /// Anything unsigned or badly signed is rejected.
+    Required,
+    /// Signatures are checked when present, unsigned files are accepted.
+    Optional,
+    /// Signatures are never checked.
+    Never
+}
+
 /// Struct form of Bulge's config file.
 #[derive(Deserialize)]
 pub(super) struct Config {
@@ -39,13 +53,92 @@ pub(super) struct Config {
     pub(super) toolchain: String,
     pub(super) colour: bool,
     pub(super) progressbar: bool,
+    /// How many packages are downloaded at once.
+    #[serde(default = "default_parallel_downloads")]
+    pub(super) parallel_downloads: usize,
+    /// Signature policy for packages installed with localinstall.
+    #[serde(default = "default_local_signatures")]
+    pub(super) local_signatures: SignaturePolicy,
+    #[serde(default)]
+    pub(super) network: NetworkConfig,
+    /// Provider picked for a virtual package when there are several and --noconfirm is used, e.g. "sh": "bash".
+    #[serde(default)]
+    pub(super) default_providers: HashMap<String, String>,
     pub(super) repos: Vec<RepoNode>
 }
 
+fn default_parallel_downloads() -> usize {
+    5
+}
+
+fn default_local_signatures() -> SignaturePolicy {
+    SignaturePolicy::Optional
+}
+
+/// Settings for every HTTP request bulge makes.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Proxy for all requests, e.g. "http://proxy:3128". Credentials can be given in the url.
+    pub proxy: Option<String>,
+    /// Hosts that are reached directly instead of through the proxy.
+    pub no_proxy: Vec<String>,
+    /// Seconds to wait for a connection to be established.
+    pub connect_timeout: u64,
+    /// Seconds a transfer may stall before it's aborted.
+    pub read_timeout: u64,
+    /// User agent sent with every request, $version is replaced with bulge's version.
+    pub user_agent: String,
+    /// PEM CA bundle trusted on top of the system one, e.g. for an internal CA.
+    pub extra_ca_bundle: Option<String>,
+    /// PEM client certificate for servers that require mTLS.
+    pub client_cert: Option<String>,
+    /// PEM private key for the client certificate, if it isn't included in client_cert.
+    pub client_key: Option<String>
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        NetworkConfig {
+            proxy: None,
+            no_proxy: vec![],
+            connect_timeout: 15,
+            read_timeout: 30,
+            user_agent: "bulge/$version".to_string(),
+            extra_ca_bundle: None,
+            client_cert: None,
+            client_key: None
+        }
+    }
+}
+
 /// Struct form of repo config.
 #[derive(Deserialize)]
 pub(super) struct RepoNode {
     pub(super) name: String,
     pub(super) active: bool,
-    pub(super) url: Option<String> 
+    pub(super) url: Option<String>,
+    /// Repos with a higher priority win when several carry the same package, ties keep config order.
+    #[serde(default)]
+    pub(super) priority: i32,
+    /// Mirrors used for this repo instead of the global mirror list.
+    pub(super) mirrors: Option<Vec<String>>,
+    /// Repos without a policy are [SignaturePolicy::Required] once a trusted key covers them, otherwise [SignaturePolicy::Optional].
+    pub(super) signatures: Option<SignaturePolicy>,
+    /// Credentials sent when fetching anything from this repo.
+    pub(super) credentials: Option<RepoCredentials>
+}
+
+/// Credentials for a private repo.
+///
+/// Either given inline or as a reference to a root-only credentials file under /etc/bulge holding one of the other forms.
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum RepoCredentials {
+    /// HTTP basic auth.
+    Basic { username: String, password: String },
+    /// Sent as "Authorization: Bearer <token>".
+    Bearer { token: String },
+    /// Path to a credentials file, resolved inside bulge's root like the rest of /etc/bulge.
+    File { file: String }
 }
\ No newline at end of file
@@ -5,7 +5,7 @@ use crate::util::config::fns::get_sources;
 use std::{error::Error, fmt};
 use crate::util::macros::get_root;
 
-use super::structs::InstalledPackages;
+use super::structs::{CachedRepo, InstallReason, InstalledPackages, RepoMaintainer, RepoMetadata};
 
 #[derive(Debug)]
 pub struct PackageDBError;
@@ -33,11 +33,14 @@ pub fn init_database() {
                 installed_files text,
                 provides text,
                 conflicts text,
-                dependencies text
+                dependencies text,
+                reason text not null default 'explicit'
             )",
         [],
     ).expect("Failed to insert installed packages table");
 
+    init_install_reason_column(&conn).expect("Failed to insert install reason column");
+
     conn.execute(
         "create table if not exists repos
             (
@@ -48,6 +51,8 @@ pub fn init_database() {
         [],
     ).expect("Failed to insert repos table");
 
+    init_repo_metadata_table(&conn).expect("Failed to insert repo metadata table");
+
     add_package_to_installed(NewPackage {
         name: "bulge".to_string(),
         groups: "core".to_string(),
@@ -57,6 +62,7 @@ pub fn init_database() {
         provides: vec!["bulge".to_string()],
         conflicts: vec![],
         dependencies: vec!["curl".to_string(), "sqlite".to_string()],
+        reason: InstallReason::Explicit,
     }, Source{
         name: "core".to_string(),
         url: None
@@ -78,9 +84,11 @@ pub fn add_package_to_installed(package: NewPackage, source: Source) {
         package_source = format!("{},{}", source.name, source.url.unwrap());
     }
 
+    init_install_reason_column(&conn).expect("Failed to insert install reason column");
+
     conn.execute("
-        INSERT OR REPLACE INTO installed_packages (name, groups, source, version, epoch, installed_files, provides, conflicts, dependencies)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9);",
+        INSERT OR REPLACE INTO installed_packages (name, groups, source, version, epoch, installed_files, provides, conflicts, dependencies, reason)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10);",
         params![package.name,
         package.groups,
         package_source,
@@ -89,10 +97,35 @@ pub fn add_package_to_installed(package: NewPackage, source: Source) {
         installed_files,
         vec_to_string(package.provides),
         vec_to_string(package.conflicts),
-        vec_to_string(package.dependencies)]
+        vec_to_string(package.dependencies),
+        package.reason.as_str()]
     ).expect("Failed to insert package into database!");
 }
 
+/// Adds the reason column to installed_packages tables from before it existed, their packages count as explicit
+fn init_install_reason_column(conn: &Connection) -> Result<(), rusqlite::Error> {
+    if conn.prepare("SELECT reason FROM installed_packages").is_err() {
+        conn.execute("ALTER TABLE installed_packages ADD COLUMN reason text not null default 'explicit'", [])?;
+    }
+
+    Ok(())
+}
+
+/// Returns why a package was installed, packages that aren't installed or predate the column count as explicit
+pub fn get_install_reason(package: &String) -> InstallReason {
+    let conn = match Connection::open(get_root() + "/etc/bulge/databases/bulge.db") {
+        Ok(conn) => conn,
+        Err(_) => return InstallReason::Explicit,
+    };
+
+    let reason: Result<String, rusqlite::Error> = conn.query_row("SELECT reason FROM installed_packages WHERE name = ?", [package], |row| row.get(0));
+
+    match reason.as_deref() {
+        Ok("dependency") => InstallReason::Dependency,
+        _ => InstallReason::Explicit,
+    }
+}
+
 /// Returns files owned by a package
 pub fn return_owned_files(package: &String) -> Result<Vec<String>, rusqlite::Error> {
     let conn = Connection::open(get_root() + "/etc/bulge/databases/bulge.db")?;
@@ -131,9 +164,9 @@ pub fn remove_package_from_installed(package: &String) -> Result<(), rusqlite::E
     Ok(())
 }
 
-/// Look for a package in a repo and return the repo it is present in
-pub fn search_for_package(package: &String) -> Result<String, PackageDBError> {
-    let mut repo = String::new();
+/// Look for a package in every repo and return the repos it is present in, highest priority first
+pub fn find_package_repos(package: &String) -> Vec<String> {
+    let mut repos: Vec<String> = vec![];
 
     for i in get_sources() {
         let conn = Connection::open(format!("{}/etc/bulge/databases/cache/{}.db", get_root(), i.name)).expect("Failed to create package database");
@@ -147,16 +180,12 @@ pub fn search_for_package(package: &String) -> Result<String, PackageDBError> {
         let mut statement = conn.prepare("SELECT * FROM packages WHERE name = ?").expect("Failed to prepare statement");
         let mut rows = statement.query([package]).expect("Failed to query database");
 
-        while let Some(_) = rows.next().expect("Failed to get next row") {
-            repo = i.name.clone();
-        }
-
-        if !repo.is_empty() {
-            return Ok(repo)
+        if rows.next().expect("Failed to get next row").is_some() {
+            repos.push(i.name.clone());
         }
     }
 
-    return Ok(repo)
+    repos
 }
 
 pub fn update_cached_repos(repo: &String, repo_hash: &String) {
@@ -177,6 +206,81 @@ pub fn update_cached_repos(repo: &String, repo_hash: &String) {
     ).expect("Failed to insert repo into database!");
 }
 
+/// Creates the table holding each repo's metadata.json, databases from before it existed get it on their next sync
+fn init_repo_metadata_table(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "create table if not exists repo_metadata
+            (
+                name text not null unique primary key,
+                display_name text,
+                maintainer_name text,
+                maintainer_email text,
+                arch text,
+                last_updated text
+            )",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// Stores the metadata.json fetched for a repo during sync
+pub fn update_repo_metadata(repo: &String, metadata: &RepoMetadata) -> Result<(), rusqlite::Error> {
+    let conn = Connection::open(get_root() + "/etc/bulge/databases/bulge.db")?;
+
+    init_repo_metadata_table(&conn)?;
+
+    conn.execute("
+        INSERT OR REPLACE INTO repo_metadata (name, display_name, maintainer_name, maintainer_email, arch, last_updated)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6);",
+        params![repo,
+        metadata.name,
+        metadata.maintainer.name,
+        metadata.maintainer.email,
+        metadata.arch,
+        metadata.last_updated]
+    )?;
+
+    Ok(())
+}
+
+/// Returns the stored metadata.json of a repo
+pub fn get_repo_metadata(repo: &String) -> Result<RepoMetadata, PackageDBError> {
+    let conn = Connection::open(get_root() + "/etc/bulge/databases/bulge.db").map_err(|_| PackageDBError)?;
+
+    conn.query_row("SELECT display_name, maintainer_name, maintainer_email, arch, last_updated FROM repo_metadata WHERE name = ?", [repo], |row| {
+        Ok(RepoMetadata {
+            name: row.get(0)?,
+            maintainer: RepoMaintainer {
+                name: row.get(1)?,
+                email: row.get(2)?
+            },
+            arch: row.get(3)?,
+            last_updated: row.get(4)?
+        })
+    }).map_err(|_| PackageDBError)
+}
+
+/// Returns the hash and time of a repo's last sync
+pub fn get_cached_repo(repo: &String) -> Result<CachedRepo, PackageDBError> {
+    let conn = Connection::open(get_root() + "/etc/bulge/databases/bulge.db").map_err(|_| PackageDBError)?;
+
+    conn.query_row("SELECT repo_hash, last_updated FROM repos WHERE name = ?", [repo], |row| {
+        Ok(CachedRepo {
+            repo_hash: row.get(0)?,
+            last_updated: row.get(1)?
+        })
+    }).map_err(|_| PackageDBError)
+}
+
+/// Returns the hash of a repo's database from the last sync
+pub fn get_cached_repo_hash(repo: &String) -> Result<String, PackageDBError> {
+    let conn = Connection::open(get_root() + "/etc/bulge/databases/bulge.db").map_err(|_| PackageDBError)?;
+
+    conn.query_row("SELECT repo_hash FROM repos WHERE name = ?", [repo], |row| row.get(0))
+        .map_err(|_| PackageDBError)
+}
+
 // TODO: Change this to provides?
 pub fn get_installed_package(package: &String) -> Result<InstalledPackages, PackageDBError> {
     let conn = Connection::open(get_root() + "/etc/bulge/databases/bulge.db").expect("Failed to open database");
@@ -241,26 +345,6 @@ pub fn get_remote_package(package: &String, repo: &String) -> Result<Package, Pa
 }
 
 
-/// Get top-level dependencies for a package
-pub fn get_dependencies(package_name: String) -> Result<Vec<Package>, PackageDBError> {
-    let mut dependencies: Vec<Package> = Vec::new();
-
-    let pkg_repo = search_for_package(&package_name)?;
-
-    let pkg = get_remote_package(&package_name, &pkg_repo)?;
-
-    if pkg.depends.is_empty() {
-        return Ok(dependencies);
-    }
-
-    for dep in pkg.depends.split(",") {
-        let dep_pkg = get_remote_package(&dep.to_string(), &pkg_repo)?;
-        dependencies.push(dep_pkg);
-    }
-
-    return Ok(dependencies);
-}
-
 pub fn get_all_installed() -> Vec<InstalledPackages> {
     let conn = Connection::open(format!("{}/etc/bulge/databases/bulge.db", get_root())).expect("Failed to open package database");
 
@@ -341,7 +425,11 @@ pub fn get_group(repo: &String, group: &String) -> Vec<Package> {
 pub fn get_provides(repo: &String, package: &String) -> Vec<Package> {
     let conn = Connection::open(format!("{}/etc/bulge/databases/cache/{}.db", get_root(), repo)).expect("Failed to open package database");
 
-    let mut statement = conn.prepare("SELECT * FROM packages WHERE instr(provides, ?) > 0;").expect("Failed to create statement");
+    // Empty repos don't have a packages table
+    let mut statement = match conn.prepare("SELECT * FROM packages WHERE instr(provides, ?) > 0;") {
+        Ok(statement) => statement,
+        Err(_) => return vec![],
+    };
 
     let result = statement.query_map([package], | package | {
         return Ok(Package{
@@ -364,26 +452,35 @@ pub fn get_provides(repo: &String, package: &String) -> Vec<Package> {
     return result.map(|r| r.unwrap()).collect();
 }
 
-pub fn get_conflicts(package: &String) -> Vec<InstalledPackages> {
-    let conn = Connection::open(format!("{}/etc/bulge/databases/bulge.db", get_root())).expect("Failed to open package database");
+/// Get packages in a repo that list a package in their replaces, this matches substrings so check the result
+pub fn get_replaces(repo: &String, package: &String) -> Vec<Package> {
+    let conn = Connection::open(format!("{}/etc/bulge/databases/cache/{}.db", get_root(), repo)).expect("Failed to open package database");
 
-    let mut statement = conn.prepare("SELECT * FROM installed_packages WHERE instr(conflicts, ?) > 0;").expect("Failed to create statement");
+    // Empty repos don't have a packages table
+    let mut statement = match conn.prepare("SELECT * FROM packages WHERE instr(replaces, ?) > 0;") {
+        Ok(statement) => statement,
+        Err(_) => return vec![],
+    };
 
     let result = statement.query_map([package], | package | {
-        return Ok(InstalledPackages{
+        Ok(Package{
             name: package.get(0).unwrap(),
-            groups: string_to_vec(package.get::<usize, String>(1).unwrap()),
-            source: package.get(2).unwrap(),
-            version: package.get(3).unwrap(),
-            epoch: package.get(4).unwrap(),
-            installed_files: package.get::<usize, String>(5).unwrap().split(",").map(|s| s.to_string()).collect(),
-            provides: string_to_vec(package.get::<usize, String>(6).unwrap()),
-            conflicts: string_to_vec(package.get::<usize, String>(7).unwrap()),
-            dependencies: string_to_vec(package.get::<usize, String>(8).unwrap()),
-        });
+            version: package.get(1).unwrap(),
+            epoch: package.get(2).unwrap(),
+            description: package.get(3).unwrap(),
+            groups: package.get(4).unwrap(),
+            url: package.get(5).unwrap(),
+            license: package.get(6).unwrap(),
+            depends: package.get(7).unwrap(),
+            optional_depends: package.get(8).unwrap(),
+            provides: package.get(9).unwrap(),
+            conflicts: package.get(10).unwrap(),
+            replaces: package.get(11).unwrap(),
+            sha512sum: package.get(12).unwrap()
+        })
     }).expect("Failed to execute query");
 
-    return result.map(|r| r.unwrap()).collect();
+    result.map(|r| r.unwrap()).collect()
 }
 
 pub fn get_depended_on(package: &String) -> Vec<InstalledPackages> {
@@ -406,4 +503,65 @@ pub fn get_depended_on(package: &String) -> Vec<InstalledPackages> {
     }).expect("Failed to execute query");
 
     return result.map(|r| r.unwrap()).collect();
-}
\ No newline at end of file
+}
+
+/// Creates the packages table of a repo database if it doesn't exist yet
+pub fn init_repo_database(path: &String) -> Result<(), rusqlite::Error> {
+    let conn = Connection::open(path)?;
+
+    conn.execute(
+        "create table if not exists packages
+            (
+                name text not null unique primary key,
+                version text not null,
+                epoch integer not null,
+                description text,
+                groups text,
+                url text,
+                license text,
+                depends text,
+                optional_depends text,
+                provides text,
+                conflicts text,
+                replaces text,
+                sha512sum text not null
+            )",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// Adds a package to a repo database, replacing any other version of it
+pub fn add_package_to_repo(path: &String, package: &Package) -> Result<(), rusqlite::Error> {
+    let conn = Connection::open(path)?;
+
+    conn.execute("
+        INSERT OR REPLACE INTO packages (name, version, epoch, description, groups, url, license, depends, optional_depends, provides, conflicts, replaces, sha512sum)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13);",
+        params![package.name,
+        package.version,
+        package.epoch,
+        package.description,
+        package.groups,
+        package.url,
+        package.license,
+        package.depends,
+        package.optional_depends,
+        package.provides,
+        package.conflicts,
+        package.replaces,
+        package.sha512sum]
+    )?;
+
+    Ok(())
+}
+
+/// Removes a package from a repo database, returns false if it wasn't in the database
+pub fn remove_package_from_repo(path: &String, package: &String) -> Result<bool, rusqlite::Error> {
+    let conn = Connection::open(path)?;
+
+    let removed = conn.execute("DELETE FROM packages WHERE name = ?1", params![package])?;
+
+    Ok(removed > 0)
+}
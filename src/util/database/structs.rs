@@ -1,3 +1,5 @@
+use serde::Deserialize;
+
 pub struct RemotePackage {
     pub name: String,
     pub version: String,
@@ -27,8 +29,51 @@ pub struct InstalledPackages {
     pub dependencies: Vec<String>
 }
 
+/// Why a package is installed.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum InstallReason {
+    /// The user asked for it.
+    Explicit,
+    /// Pulled in to satisfy another package's depends.
+    Dependency
+}
+
+impl InstallReason {
+    /// Form stored in the reason column of bulge.db.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InstallReason::Explicit => "explicit",
+            InstallReason::Dependency => "dependency",
+        }
+    }
+}
+
 #[derive(PartialEq, Eq, Hash, Clone)]
 pub struct Source {
     pub name: String,
     pub url: Option<String>
+}
+
+/// Repo level metadata.json, published next to a repo's database.
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct RepoMetadata {
+    pub name: String,
+    pub maintainer: RepoMaintainer,
+    pub arch: String,
+    #[serde(rename = "lastUpdated")]
+    pub last_updated: String
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct RepoMaintainer {
+    pub name: String,
+    pub email: String
+}
+
+/// What bulge.db knows about a repo from its last sync.
+pub struct CachedRepo {
+    pub repo_hash: String,
+    pub last_updated: String
 }
\ No newline at end of file
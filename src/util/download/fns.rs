@@ -0,0 +1,300 @@
+use std::fs::{self, OpenOptions};
+use std::io::{copy, Read};
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::Duration;
+
+use isahc::auth::{Authentication, Credentials};
+use isahc::http::StatusCode;
+use isahc::http::request::Builder;
+use isahc::prelude::*;
+
+use crate::util::config::structs::RepoCredentials;
+use crate::util::download::structs::DownloadError;
+use crate::util::macros::{is_offline, request};
+
+/// How many times a single url is tried before moving on
+const RETRIES: u32 = 3;
+
+/// Delay before the first retry, doubled after every failed attempt
+const BACKOFF: Duration = Duration::from_secs(1);
+
+/// Errors that won't go away by asking the same server again
+fn is_permanent(error: &DownloadError) -> bool {
+    match error {
+        DownloadError::Status(status) => status.is_client_error()
+            && *status != StatusCode::REQUEST_TIMEOUT
+            && *status != StatusCode::TOO_MANY_REQUESTS
+            && *status != StatusCode::RANGE_NOT_SATISFIABLE,
+        _ => false,
+    }
+}
+
+/// Returns the path on disk for `file://` urls and bare absolute paths
+///
+/// `file://` urls need an empty host or `localhost`, like `file:///srv/repo` or `file://localhost/srv/repo`.
+fn get_local_path(url: &str) -> Option<&Path> {
+    match url.strip_prefix("file://") {
+        Some(path) if path.starts_with('/') => Some(Path::new(path)),
+        Some(path) => path.strip_prefix("localhost").filter(|p| p.starts_with('/')).map(Path::new),
+        None if url.starts_with('/') => Some(Path::new(url)),
+        None => None,
+    }
+}
+
+/// Strip any credentials embedded in a url so it's safe to print
+pub fn redact_url(url: &str) -> String {
+    let (scheme, rest) = match url.split_once("://") {
+        Some(parts) => parts,
+        None => return url.to_string(),
+    };
+
+    let authority_end = rest.find('/').unwrap_or(rest.len());
+
+    match rest[..authority_end].rfind('@') {
+        Some(at) => format!("{}://***@{}", scheme, &rest[at + 1..]),
+        None => url.to_string(),
+    }
+}
+
+/// Attach a repo's credentials to a request
+fn authenticate(builder: Builder, auth: Option<&RepoCredentials>) -> Builder {
+    match auth {
+        Some(RepoCredentials::Basic { username, password }) => builder
+            .authentication(Authentication::basic())
+            .credentials(Credentials::new(username.as_str(), password.as_str())),
+        Some(RepoCredentials::Bearer { token }) => builder.header("Authorization", format!("Bearer {}", token)),
+        // Files are resolved by get_repo_credentials before anything is downloaded
+        Some(RepoCredentials::File { .. }) | None => builder,
+    }
+}
+
+/// Run a download attempt, retrying with exponential backoff
+fn with_retries<T, F: FnMut() -> Result<T, DownloadError>>(url: &str, mut attempt: F) -> Result<T, DownloadError> {
+    let mut delay = BACKOFF;
+
+    for i in 1..=RETRIES {
+        match attempt() {
+            Ok(result) => return Ok(result),
+            Err(e) if is_permanent(&e) || i == RETRIES => return Err(e),
+            Err(e) => {
+                println!("Failed to get {}. Error: {} (retrying in {}s)", redact_url(url), e, delay.as_secs());
+
+                sleep(delay);
+                delay *= 2;
+            }
+        }
+    }
+
+    unreachable!()
+}
+
+/// Where a download to `dest` is kept until it's complete
+fn part_path(dest: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.part", dest.display()))
+}
+
+/// Single attempt at downloading a url to `<dest>.part`, continuing from whatever is already there
+fn try_download_file(url: &str, part: &Path, auth: Option<&RepoCredentials>) -> Result<(), DownloadError> {
+    let existing = fs::metadata(part).map(|m| m.len()).unwrap_or(0);
+
+    let mut builder = authenticate(request(url), auth);
+
+    if existing > 0 {
+        builder = builder.header("Range", format!("bytes={}-", existing));
+    }
+
+    let mut response = builder.body(())?.send()?;
+
+    let mut file = match response.status() {
+        StatusCode::OK => OpenOptions::new().create(true).write(true).truncate(true).open(part)?,
+        StatusCode::PARTIAL_CONTENT => OpenOptions::new().append(true).open(part)?,
+        StatusCode::RANGE_NOT_SATISFIABLE => {
+            // The partial file doesn't match what the server has, start over on the next attempt
+            fs::remove_file(part)?;
+
+            return Err(DownloadError::Status(response.status()));
+        },
+        status => return Err(DownloadError::Status(status)),
+    };
+
+    copy(response.body_mut(), &mut file)?;
+    file.sync_all()?;
+
+    Ok(())
+}
+
+/// Download a url to a file, partial downloads are resumed with a HTTP Range request.
+///
+/// Local urls are copied straight from disk, `auth` is only sent to remote ones and those fail with --offline.
+pub fn download_file(url: &str, dest: &Path, auth: Option<&RepoCredentials>) -> Result<(), DownloadError> {
+    let part = part_path(dest);
+
+    match get_local_path(url) {
+        Some(path) => {
+            fs::copy(path, &part)?;
+        },
+        None if is_offline() => return Err(DownloadError::Offline),
+        None => with_retries(url, || try_download_file(url, &part, auth))?,
+    }
+
+    fs::rename(&part, dest)?;
+
+    Ok(())
+}
+
+/// Download a url into memory, meant for small files like hashes and signatures
+pub fn download_bytes(url: &str, auth: Option<&RepoCredentials>) -> Result<Vec<u8>, DownloadError> {
+    if let Some(path) = get_local_path(url) {
+        return Ok(fs::read(path)?);
+    }
+
+    if is_offline() {
+        return Err(DownloadError::Offline);
+    }
+
+    with_retries(url, || {
+        let mut response = authenticate(request(url), auth).body(())?.send()?;
+
+        if response.status() != StatusCode::OK {
+            return Err(DownloadError::Status(response.status()));
+        }
+
+        let mut bytes: Vec<u8> = vec![];
+        response.body_mut().read_to_end(&mut bytes)?;
+
+        Ok(bytes)
+    })
+}
+
+/// Try each url in order until one of them downloads and passes `verify`.
+///
+/// Network errors, bad statuses and failed verification are all treated as a broken mirror.
+/// A resumed download that fails verification is fetched again from scratch before giving up on the mirror.
+/// Returns the url that was used.
+pub fn download_from_mirrors<F: Fn(&Path) -> bool>(urls: &[String], dest: &Path, auth: Option<&RepoCredentials>, verify: F) -> Result<String, DownloadError> {
+    for url in urls {
+        // A leftover .part may be from an older copy of the file or another mirror
+        let mut fresh = !part_path(dest).exists();
+
+        loop {
+            if let Err(e) = download_file(url, dest, auth) {
+                println!("Failed to get {}. Error: {}", redact_url(url), e);
+                break;
+            }
+
+            if verify(dest) {
+                return Ok(url.clone());
+            }
+
+            fs::remove_file(dest)?;
+
+            if fresh {
+                println!("!!!> Verification failed for {}, trying next mirror. <!!!", redact_url(url));
+                break;
+            }
+
+            // Only the resumed part could be bad, so give the mirror one more go from the start
+            println!("!!!> Verification failed for resumed download of {}, starting over. <!!!", redact_url(url));
+            fresh = true;
+        }
+    }
+
+    Err(DownloadError::NoMirror)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fresh directory under the system temp dir, unique to this test run
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bulge-test-{}-{}", name, std::process::id()));
+
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn parses_local_urls() {
+        assert_eq!(get_local_path("file:///srv/repo"), Some(Path::new("/srv/repo")));
+        assert_eq!(get_local_path("file://localhost/srv/repo"), Some(Path::new("/srv/repo")));
+        assert_eq!(get_local_path("/srv/repo"), Some(Path::new("/srv/repo")));
+        assert_eq!(get_local_path("file://otherhost/srv/repo"), None);
+        assert_eq!(get_local_path("file://localhostname/srv/repo"), None);
+        assert_eq!(get_local_path("https://repo.example.com/core"), None);
+    }
+
+    #[test]
+    fn falls_back_to_the_next_local_mirror() {
+        let dir = temp_dir("mirrors");
+
+        // One mirror is missing the file, one has a corrupt copy and the last one is good
+        fs::create_dir_all(dir.join("corrupt")).unwrap();
+        fs::create_dir_all(dir.join("good")).unwrap();
+        fs::write(dir.join("corrupt/database.db"), b"corrupt").unwrap();
+        fs::write(dir.join("good/database.db"), b"database").unwrap();
+
+        let urls: Vec<String> = vec![
+            format!("{}/missing/database.db", dir.display()),
+            format!("file://{}/corrupt/database.db", dir.display()),
+            format!("file://localhost{}/good/database.db", dir.display())
+        ];
+
+        let dest = dir.join("database.db.tmp");
+        let url = download_from_mirrors(&urls, &dest, None, |p| fs::read(p).is_ok_and(|c| c == b"database")).unwrap();
+
+        assert_eq!(url, urls[2]);
+        assert_eq!(fs::read(&dest).unwrap(), b"database");
+        assert!(!part_path(&dest).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fails_when_no_local_mirror_verifies() {
+        let dir = temp_dir("no-mirror");
+
+        fs::write(dir.join("database.db"), b"corrupt").unwrap();
+
+        let urls: Vec<String> = vec![format!("file://{}/database.db", dir.display())];
+        let dest = dir.join("database.db.tmp");
+
+        assert!(matches!(download_from_mirrors(&urls, &dest, None, |_| false), Err(DownloadError::NoMirror)));
+        assert!(!dest.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reads_local_bytes() {
+        let dir = temp_dir("bytes");
+
+        fs::write(dir.join("database.hash"), b"abc123").unwrap();
+
+        assert_eq!(download_bytes(&format!("file://{}/database.hash", dir.display()), None).unwrap(), b"abc123");
+        assert_eq!(download_bytes(&format!("{}/database.hash", dir.display()), None).unwrap(), b"abc123");
+        assert!(download_bytes(&format!("{}/missing.hash", dir.display()), None).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn redacts_credentials() {
+        assert_eq!(redact_url("https://user:pw@repo.example.com/core/x86_64"), "https://***@repo.example.com/core/x86_64");
+        assert_eq!(redact_url("http://token@proxy:3128"), "http://***@proxy:3128");
+        // Only the last @ before the path ends the credentials
+        assert_eq!(redact_url("https://user:p@ss@host/path"), "https://***@host/path");
+    }
+
+    #[test]
+    fn leaves_other_urls_alone() {
+        for url in ["https://repo.example.com/core", "https://host/users/@me", "file:///var/cache/core", "/srv/repo", "not a url"] {
+            assert_eq!(redact_url(url), url);
+        }
+    }
+}
@@ -0,0 +1,2 @@
+pub mod fns;
+pub mod structs;
@@ -0,0 +1,50 @@
+use std::{error::Error, fmt};
+
+use isahc::http::StatusCode;
+
+/// Reasons a download can fail.
+#[derive(Debug)]
+pub enum DownloadError {
+    /// The request never got a response, DNS, TCP and timeout errors end up here.
+    Network(isahc::Error),
+    /// The server responded with something we can't use.
+    Status(StatusCode),
+    /// The response couldn't be read or written to disk.
+    Io(std::io::Error),
+    /// No mirror had a usable copy.
+    NoMirror,
+    /// A remote url was requested while running with --offline.
+    Offline
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DownloadError::Network(e) => write!(f, "{}", e),
+            DownloadError::Status(status) => write!(f, "Status: {}", status),
+            DownloadError::Io(e) => write!(f, "{}", e),
+            DownloadError::NoMirror => write!(f, "No mirror has a usable copy!"),
+            DownloadError::Offline => write!(f, "Network access is disabled by --offline!"),
+        }
+    }
+}
+
+impl Error for DownloadError {}
+
+impl From<isahc::Error> for DownloadError {
+    fn from(e: isahc::Error) -> Self {
+        DownloadError::Network(e)
+    }
+}
+
+impl From<isahc::http::Error> for DownloadError {
+    fn from(e: isahc::http::Error) -> Self {
+        DownloadError::Network(e.into())
+    }
+}
+
+impl From<std::io::Error> for DownloadError {
+    fn from(e: std::io::Error) -> Self {
+        DownloadError::Io(e)
+    }
+}
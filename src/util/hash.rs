@@ -0,0 +1,20 @@
+use std::io::Read;
+
+use hex::ToHex;
+use ring::digest::{Context, SHA512};
+
+/// Reads everything from a reader and returns the hex encoded SHA-512 digest
+pub fn sha512_hex<R: Read>(mut reader: R) -> std::io::Result<String> {
+    let mut context = Context::new(&SHA512);
+    let mut buffer = [0; 1024];
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        context.update(&buffer[..read]);
+    }
+
+    Ok(context.finish().as_ref().encode_hex::<String>())
+}
@@ -1,10 +1,20 @@
-use std::collections::{HashMap, HashSet};
-use std::{env, io};
+use std::collections::HashSet;
+use std::{env, fs, io};
+use std::fs::{DirBuilder, OpenOptions};
 use std::io::Write;
-use isahc::{Body, Request, Response};
-use isahc::config::RedirectPolicy;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use isahc::Request;
+use isahc::http::request::Builder;
+use isahc::config::{CaCertificate, ClientCertificate, PrivateKey, RedirectPolicy};
 use isahc::prelude::*;
+use crate::util::config::fns::get_network_config;
+use crate::util::config::structs::NetworkConfig;
 use crate::util::database::structs::InstalledPackages;
+use crate::util::lock::remove_lock;
 use crate::util::packaging::structs::Package;
 
 /// Converts a vec of strings to a flat string separated by ","
@@ -21,9 +31,9 @@ pub fn vec_to_string(vec: Vec<String>) -> String {
     temp_string
 }
 
-pub fn display_installing_packages(set: HashMap<Package, String>) -> String {
+pub fn display_installing_packages(queue: &[(Package, String)]) -> String {
     let mut temp_string: String = String::new();
-    for i in set {
+    for i in queue {
         temp_string.push_str(&*i.0.name);
         temp_string.push_str("-");
         temp_string.push_str(&*i.0.version);
@@ -60,17 +70,140 @@ pub fn get_root() -> String {
     }
 }
 
-/// Default isahc get
-pub fn get(url: &String) -> Result<Response<Body>, isahc::Error> {
-    return Request::get(url)
-            .redirect_policy(RedirectPolicy::Follow)
-            .body(())?
-            .send();
+/// Usual locations of the system CA bundle
+const SYSTEM_CA_BUNDLES: [&str; 4] = [
+    "/etc/ssl/certs/ca-certificates.crt",
+    "/etc/pki/tls/certs/ca-bundle.crt",
+    "/etc/ssl/ca-bundle.pem",
+    "/etc/ssl/cert.pem"
+];
+
+/// Network section of the config, only read once per run
+static NETWORK: OnceLock<NetworkConfig> = OnceLock::new();
+
+/// Combined bundle for this run, only built once
+static CA_BUNDLE: OnceLock<Result<PathBuf, String>> = OnceLock::new();
+
+/// Append an extra CA bundle to the system one, curl only takes a single bundle so setting ours alone
+/// would stop every other server from being trusted.
+fn combined_ca_bundle(extra_ca_bundle: &String) -> Result<PathBuf, String> {
+    CA_BUNDLE.get_or_init(|| {
+        let mut bundle = match SYSTEM_CA_BUNDLES.iter().find(|p| Path::new(p).exists()) {
+            Some(system) => fs::read(system).map_err(|e| e.to_string())?,
+            None => {
+                println!("WARN> No system CA bundle found, only trusting {}", extra_ca_bundle);
+                vec![]
+            }
+        };
+
+        bundle.push(b'\n');
+        bundle.append(&mut fs::read(extra_ca_bundle).map_err(|e| e.to_string())?);
+
+        // Kept out of the world writable /tmp so nobody else can swap in their own CA
+        let dir = PathBuf::from(format!("{}/var/cache/bulge/ca", get_root()));
+        DirBuilder::new().recursive(true).mode(0o700).create(&dir).map_err(|e| e.to_string())?;
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700)).map_err(|e| e.to_string())?;
+
+        // Write then rename so a half written bundle is never used
+        let path = dir.join("ca-bundle.pem");
+        let part = dir.join(format!("ca-bundle.pem.{}", std::process::id()));
+
+        // A leftover file or symlink is never written through
+        if fs::symlink_metadata(&part).is_ok() {
+            fs::remove_file(&part).map_err(|e| e.to_string())?;
+        }
+
+        OpenOptions::new().write(true).create_new(true).mode(0o600).open(&part)
+            .and_then(|mut f| f.write_all(&bundle))
+            .and_then(|_| fs::rename(&part, &path))
+            .map_err(|e| e.to_string())?;
+
+        Ok(path)
+    }).clone()
+}
+
+/// Default isahc request builder, everything bulge fetches should start from this.
+///
+/// Proxy, timeouts, user agent and TLS settings come from the network section of the config.
+pub fn request(url: &str) -> Builder {
+    let network = NETWORK.get_or_init(get_network_config).clone();
+
+    let mut builder = Request::get(url)
+        .redirect_policy(RedirectPolicy::Follow)
+        .connect_timeout(Duration::from_secs(network.connect_timeout))
+        // Abort transfers that stall instead of hanging forever
+        .low_speed_timeout(1, Duration::from_secs(network.read_timeout))
+        .header("User-Agent", network.user_agent.replace("$version", crate::get_version()));
+
+    if let Some(proxy) = network.proxy {
+        match proxy.parse::<isahc::http::Uri>() {
+            Ok(proxy) => builder = builder.proxy(proxy).proxy_blacklist(network.no_proxy),
+            Err(e) => {
+                // Going around a configured proxy could leak traffic, so don't fall back to a direct connection
+                // The url can hold credentials, so only the parse error is shown
+                eprintln!("ERR> Invalid proxy in config: {}", e);
+
+                remove_lock().ok();
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(extra_ca_bundle) = network.extra_ca_bundle {
+        match combined_ca_bundle(&extra_ca_bundle) {
+            Ok(bundle) => builder = builder.ssl_ca_certificate(CaCertificate::file(bundle)),
+            Err(e) => {
+                eprintln!("ERR> Failed to load extra CA bundle {}: {}", extra_ca_bundle, e);
+
+                remove_lock().ok();
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(client_cert) = network.client_cert {
+        let client_key = network.client_key.map(|k| PrivateKey::pem_file(k, None));
+
+        builder = builder.ssl_client_certificate(ClientCertificate::pem_file(client_cert, client_key));
+    }
+
+    builder
+}
+
+/// Set by --noconfirm, answers every prompt with its default, only [transaction_prompt] is answered with yes
+static NOCONFIRM: AtomicBool = AtomicBool::new(false);
+
+/// Stop prompting the user for the rest of this run
+pub fn set_noconfirm(noconfirm: bool) {
+    NOCONFIRM.store(noconfirm, Ordering::Relaxed);
+}
+
+/// Returns true if --noconfirm was given
+pub fn is_noconfirm() -> bool {
+    NOCONFIRM.load(Ordering::Relaxed)
+}
+
+/// Set by --offline, any attempt to reach the network fails instead
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Forbid network access for the rest of this run
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+/// Returns true if --offline was given
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
 }
 
 pub fn continue_prompt() -> bool {
     let mut input = String::new();
 
+    if is_noconfirm() {
+        println!("Continue? [y/N]: n");
+        return false;
+    }
+
     print!("Continue? [y/N]: ");
 
     io::stdout().flush().unwrap();
@@ -81,4 +214,42 @@ pub fn continue_prompt() -> bool {
     }
 
     return false;
-}
\ No newline at end of file
+}
+
+/// Ask the user to go ahead with a transaction, --noconfirm answers yes.
+///
+/// Only the final confirmation uses this, prompts about downgrades or overwriting files still default to no.
+pub fn transaction_prompt() -> bool {
+    if is_noconfirm() {
+        println!("Continue? [y/N]: y");
+        return true;
+    }
+
+    continue_prompt()
+}
+
+/// Ask the user to pick one of several options by number, returns the index of the pick.
+///
+/// Empty or invalid input picks `default`, as does --noconfirm.
+pub fn number_prompt(options: &[String], default: usize) -> usize {
+    for (i, option) in options.iter().enumerate() {
+        println!("\t{}) {}", i + 1, option);
+    }
+
+    if is_noconfirm() {
+        println!("Enter a number (default={}): {}", default + 1, default + 1);
+        return default;
+    }
+
+    let mut input = String::new();
+
+    print!("Enter a number (default={}): ", default + 1);
+
+    io::stdout().flush().unwrap();
+    io::stdin().read_line(&mut input).unwrap();
+
+    match input.trim().parse::<usize>() {
+        Ok(i) if i >= 1 && i <= options.len() => i - 1,
+        _ => default,
+    }
+}
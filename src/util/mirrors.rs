@@ -1,33 +1,61 @@
 use std::fs::File;
 use std::io::prelude::*;
-use crate::util::config::fns::get_config_entry;
+use crate::util::config::fns::{get_config_entry, get_repo_mirrors, get_sources};
 use crate::util::config::structs::ConfigEntries;
 use crate::util::macros::get_root;
 
+/// Replace the $arch and $toolchain variables in a mirror
+fn expand_mirror(mirror: &str) -> Result<String, String> {
+    let arch = get_config_entry(ConfigEntries::Architecture, None, None)
+        .map_err(|e| format!("Failed to get config architecture: {}", e))?;
+    let toolchain = get_config_entry(ConfigEntries::Toolchain, None, None)
+        .map_err(|e| format!("Failed to get config toolchain: {}", e))?;
+
+    Ok(mirror.trim()
+        .replace("$arch", arch.trim_matches(|c| c == '\\' || c == '"'))
+        .replace("$toolchain", toolchain.trim_matches(|c| c == '\\' || c == '"')))
+}
+
 /// Load mirrors for repos from mirror list
-pub fn load_mirrors() -> Vec<String> {
+///
+/// Errors are returned rather than panicking, this runs on download threads while the lock is held.
+pub fn load_mirrors() -> Result<Vec<String>, String> {
     let mut mirrors: Vec<String> = vec![];
 
-    let arch = get_config_entry(ConfigEntries::Architecture, None, None).expect("Failed to get config architecture.");
-    let toolchain = get_config_entry(ConfigEntries::Toolchain, None, None).expect("Failed to get config toolchain.");
-
     let mut raw_mirrors = String::new();
 
     File::open(get_root() + "/etc/bulge/mirrors")
-        .expect("Failed to open mirror list, is another program using it?")
-        .read_to_string(&mut raw_mirrors)
-        .expect("Failed to convert file to string");
+        .and_then(|mut f| f.read_to_string(&mut raw_mirrors))
+        .map_err(|e| format!("Failed to read mirror list: {}", e))?;
 
     for i in raw_mirrors.lines() {
         if !i.is_empty() && !i.starts_with("#") {
-            mirrors.push(
-                i.to_string()
-                    .trim()
-                    .replace("$arch", arch.trim_matches(|c| c == '\\' || c == '"'))
-                    .replace("$toolchain", toolchain.trim_matches(|c| c == '\\' || c == '"'))
-            );
+            mirrors.push(expand_mirror(i)?);
+        }
+    }
+
+    Ok(mirrors)
+}
+
+/// Returns every base url a repo can be fetched from, in the order they should be tried.
+///
+/// A repo's custom url comes first, followed by its own mirror list. Only repos with neither use the global mirror list.
+pub fn get_repo_urls(repo: &String) -> Result<Vec<String>, String> {
+    let mut urls: Vec<String> = vec![];
+
+    if let Some(url) = get_sources().into_iter().find(|s| &s.name == repo).and_then(|s| s.url) {
+        urls.push(url);
+    }
+
+    if let Some(mirrors) = get_repo_mirrors(repo) {
+        for i in mirrors {
+            urls.push(expand_mirror(&i)?.replace("$repo", repo));
         }
     }
 
-    return mirrors;
-}
\ No newline at end of file
+    if urls.is_empty() {
+        urls = load_mirrors()?.iter().map(|m| m.replace("$repo", repo)).collect();
+    }
+
+    Ok(urls)
+}
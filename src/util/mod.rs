@@ -5,4 +5,10 @@ pub mod database;
 pub mod config;
 pub mod mirrors;
 pub mod macros;
-pub mod transactions;
\ No newline at end of file
+pub mod transactions;
+pub mod hash;
+pub mod signing;
+pub mod download;
+pub mod cache;
+pub mod build;
+pub mod version;
@@ -1,8 +1,11 @@
 use std::fs::{self, File};
-use std::path::Path;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 
-use tar::Archive;
+use tar::{Archive, Builder, EntryType, Header};
 use xz2::read::XzDecoder;
+use xz2::write::XzEncoder;
 
 use crate::util::{database::fns::{remove_package_from_installed, return_owned_files}, packaging::structs::Package};
 
@@ -16,16 +19,71 @@ pub fn decode_pkg_file(pkg: File) -> Package {
     return v;
 }
 
-pub fn check_if_package(mut xztar: Archive<XzDecoder<File>>) -> bool {    
-    // Look for PKG file
-    for file in xztar.entries().unwrap() {
-        if file.unwrap().header().path().unwrap() == Path::new("PKG") {
-            // If a PKG file is found then this is a valid package
-            return true;
-        }                
+/// Entries a package archive may contain, nothing else is accepted
+const PACKAGE_ENTRIES: [&str; 3] = ["PKG", "PKG.sig", "data.tar.xz"];
+
+/// Walk a package archive, handing every entry to `f` along with its name.
+///
+/// The archive has to hold exactly one regular `PKG` and `data.tar.xz` and at most one `PKG.sig`. Paths are compared
+/// as stored, so `./PKG` or a second `PKG` is an error instead of something that could overwrite the checked file.
+pub fn read_package_entries<F: FnMut(&str, &mut dyn Read) -> io::Result<()>>(package: File, mut f: F) -> io::Result<()> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    let mut seen: Vec<&str> = vec![];
+    let mut xztar = decompress_xz(package);
+
+    for entry in xztar.entries()? {
+        let mut entry = entry?;
+        let path = entry.path_bytes().into_owned();
+
+        let name = match PACKAGE_ENTRIES.iter().find(|n| n.as_bytes() == path.as_slice()) {
+            Some(name) if entry.header().entry_type().is_file() => *name,
+            _ => return Err(invalid(format!("unexpected entry {}", String::from_utf8_lossy(&path)))),
+        };
+
+        if seen.contains(&name) {
+            return Err(invalid(format!("duplicate entry {}", name)));
+        }
+
+        seen.push(name);
+        f(name, &mut entry)?;
     }
 
-    return false;
+    for name in ["PKG", "data.tar.xz"] {
+        if !seen.contains(&name) {
+            return Err(invalid(format!("missing {}", name)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Extract the PKG and data.tar.xz of a package archive into a directory, see [read_package_entries]
+pub fn extract_package(package: File, dest: &Path) -> io::Result<()> {
+    fs::create_dir_all(dest)?;
+
+    read_package_entries(package, |name, entry| {
+        if name != "PKG.sig" {
+            io::copy(entry, &mut File::create(dest.join(name))?)?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Read the PKG file out of a package archive without unpacking it
+pub fn read_pkg_from_archive(package: File) -> Option<Package> {
+    let mut pkg: Option<Package> = None;
+
+    read_package_entries(package, |name, entry| {
+        if name == "PKG" {
+            pkg = Some(serde_json::from_reader(entry)?);
+        }
+
+        Ok(())
+    }).ok()?;
+
+    pkg
 }
 
 pub fn run_remove(package: &String) {
@@ -36,4 +94,215 @@ pub fn run_remove(package: &String) {
     }
 
     remove_package_from_installed(package).expect("Failed to remove package from database.");
-}
\ No newline at end of file
+}
+
+/// Check that a PKG file can be turned into an installable package
+pub fn validate_package(package: &Package) -> Result<(), String> {
+    let valid_name = |s: &String| !s.is_empty()
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || "@._+-".contains(c));
+
+    if !valid_name(&package.name) {
+        return Err(format!("\"{}\" is not a valid package name", package.name));
+    }
+
+    if !valid_name(&package.version) {
+        return Err(format!("\"{}\" is not a valid version", package.version));
+    }
+
+    if package.epoch < 0 {
+        return Err(format!("Epoch {} can't be negative", package.epoch));
+    }
+
+    Ok(())
+}
+
+/// Recursively collect every path under a directory, sorted so archives are always built in the same order
+fn collect_paths(root: &Path, dir: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<io::Result<Vec<PathBuf>>>()?;
+
+    entries.sort();
+
+    for i in entries {
+        paths.push(i.strip_prefix(root).expect("Path escaped the root!").to_path_buf());
+
+        if fs::symlink_metadata(&i)?.is_dir() {
+            collect_paths(root, &i, paths)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns a tar header with everything but the mode normalised, so the same tree always gives the same archive
+fn normalised_header(entry_type: EntryType, mode: u32, size: u64) -> Header {
+    let mut header = Header::new_gnu();
+
+    header.set_entry_type(entry_type);
+    header.set_mode(mode);
+    header.set_size(size);
+    header.set_mtime(0);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_username("root").expect("Failed to set tar username!");
+    header.set_groupname("root").expect("Failed to set tar group name!");
+
+    header
+}
+
+/// Write a tar.xz of a directory with sorted entries, zeroed mtimes and root ownership
+pub fn create_data_tar(root: &Path, dest: &Path) -> io::Result<()> {
+    let mut paths: Vec<PathBuf> = vec![];
+    collect_paths(root, root, &mut paths)?;
+
+    let mut builder = Builder::new(XzEncoder::new(File::create(dest)?, 6));
+
+    for i in paths {
+        let source = root.join(&i);
+        let metadata = fs::symlink_metadata(&source)?;
+        let mode = metadata.permissions().mode() & 0o7777;
+
+        if metadata.file_type().is_symlink() {
+            let mut header = normalised_header(EntryType::Symlink, mode, 0);
+            builder.append_link(&mut header, &i, fs::read_link(&source)?)?;
+        } else if metadata.is_dir() {
+            let mut header = normalised_header(EntryType::Directory, mode, 0);
+            builder.append_data(&mut header, format!("{}/", i.display()), io::empty())?;
+        } else {
+            let mut header = normalised_header(EntryType::Regular, mode, metadata.len());
+            builder.append_data(&mut header, &i, File::open(&source)?)?;
+        }
+    }
+
+    builder.into_inner()?.finish()?.sync_all()
+}
+
+/// Build a package archive from a staged directory.
+///
+/// The archive contains the PKG file and data.tar.xz, it's written to `<out_dir>/<name>-<version>-<epoch>.tar.xz`.
+pub fn create_package(mut package: Package, root: &Path, out_dir: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(out_dir)?;
+
+    let archive_path = out_dir.join(format!("{}-{}-{}.tar.xz", &package.name, &package.version, &package.epoch));
+    let data_path = out_dir.join(format!("{}-{}-{}.data.tar.xz", &package.name, &package.version, &package.epoch));
+
+    create_data_tar(root, &data_path)?;
+
+    // An archive can't hold its own hash, repo-add fills it in for the repo database
+    package.sha512sum = String::new();
+
+    let pkg = serde_json::to_vec_pretty(&package)?;
+
+    let mut builder = Builder::new(XzEncoder::new(File::create(&archive_path)?, 6));
+
+    let mut header = normalised_header(EntryType::Regular, 0o644, pkg.len() as u64);
+    builder.append_data(&mut header, "PKG", pkg.as_slice())?;
+
+    let mut header = normalised_header(EntryType::Regular, 0o644, fs::metadata(&data_path)?.len());
+    builder.append_data(&mut header, "data.tar.xz", File::open(&data_path)?)?;
+
+    builder.into_inner()?.finish()?.flush()?;
+
+    fs::remove_file(&data_path)?;
+
+    Ok(archive_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Archive entries as (name, contents)
+    type Entries<'a> = &'a [(&'a str, &'a [u8])];
+
+    /// Write a package archive with the given entries, names are stored exactly as given
+    fn write_archive(name: &str, entries: Entries) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bulge-test-package-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("package.tar.xz");
+        let mut builder = Builder::new(XzEncoder::new(File::create(&path).unwrap(), 6));
+
+        for (entry_name, contents) in entries {
+            let mut header = normalised_header(EntryType::Regular, 0o644, contents.len() as u64);
+
+            // Bypass set_path, it would clean up names like ./PKG
+            let raw_name = &mut header.as_old_mut().name;
+            raw_name.fill(0);
+            raw_name[..entry_name.len()].copy_from_slice(entry_name.as_bytes());
+            header.set_cksum();
+
+            builder.append(&header, *contents).unwrap();
+        }
+
+        builder.into_inner().unwrap().finish().unwrap();
+
+        path
+    }
+
+    fn entry_names(path: &Path) -> io::Result<Vec<String>> {
+        let mut names: Vec<String> = vec![];
+
+        read_package_entries(File::open(path)?, |name, _| {
+            names.push(name.to_string());
+            Ok(())
+        })?;
+
+        Ok(names)
+    }
+
+    #[test]
+    fn reads_package_entries() {
+        let path = write_archive("valid", &[("PKG", b"{}"), ("PKG.sig", b"00"), ("data.tar.xz", b"data")]);
+
+        assert_eq!(entry_names(&path).unwrap(), ["PKG", "PKG.sig", "data.tar.xz"]);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn rejects_tampered_archives() {
+        let cases: [(&str, Entries); 5] = [
+            // A second copy would overwrite the checked one when unpacked
+            ("duplicate", &[("PKG", b"signed"), ("data.tar.xz", b"data"), ("PKG", b"tampered")]),
+            ("dot-slash", &[("PKG", b"signed"), ("data.tar.xz", b"data"), ("./PKG", b"tampered")]),
+            ("extra", &[("PKG", b"signed"), ("data.tar.xz", b"data"), ("etc/passwd", b"tampered")]),
+            ("missing-data", &[("PKG", b"signed")]),
+            ("missing-pkg", &[("data.tar.xz", b"data")])
+        ];
+
+        for (name, entries) in cases {
+            let path = write_archive(name, entries);
+
+            assert!(entry_names(&path).is_err(), "{} was accepted", name);
+
+            fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn extracts_only_checked_entries() {
+        let path = write_archive("extract", &[("PKG", b"{}"), ("PKG.sig", b"00"), ("data.tar.xz", b"data")]);
+        let dest = path.parent().unwrap().join("out");
+
+        extract_package(File::open(&path).unwrap(), &dest).unwrap();
+
+        assert_eq!(fs::read(dest.join("PKG")).unwrap(), b"{}");
+        assert_eq!(fs::read(dest.join("data.tar.xz")).unwrap(), b"data");
+        assert!(!dest.join("PKG.sig").exists());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn does_not_extract_duplicates() {
+        let path = write_archive("extract-duplicate", &[("PKG", b"signed"), ("data.tar.xz", b"data"), ("PKG", b"tampered")]);
+        let dest = path.parent().unwrap().join("out");
+
+        assert!(extract_package(File::open(&path).unwrap(), &dest).is_err());
+        assert_ne!(fs::read(dest.join("PKG")).unwrap(), b"tampered");
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
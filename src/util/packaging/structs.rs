@@ -1,6 +1,8 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, PartialEq, Eq, Hash, Clone, Debug)]
+use crate::util::database::structs::InstallReason;
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Debug)]
 pub struct Package {
     pub name: String,
     pub version: String,
@@ -14,6 +16,10 @@ pub struct Package {
     pub provides: String,
     pub conflicts: String,
     pub replaces: String,
+    /// SHA-512 in hex of the whole package archive, filled in by `bulge repo-add` and checked on download.
+    ///
+    /// Only set in repo databases, it's always empty in a PKG file.
+    #[serde(default)]
     pub sha512sum: String
 }
 
@@ -25,7 +31,8 @@ pub struct NewPackage {
     pub installed_files: Vec<String>,
     pub provides: Vec<String>,
     pub conflicts: Vec<String>,
-    pub dependencies: Vec<String>
+    pub dependencies: Vec<String>,
+    pub reason: InstallReason
 }
 
 pub struct RequestPackage {
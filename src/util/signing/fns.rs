@@ -0,0 +1,186 @@
+use std::fs;
+use std::fs::File;
+use std::path::Path;
+
+use hex::ToHex;
+use ring::digest::{digest, SHA256};
+use ring::signature::{Ed25519KeyPair, UnparsedPublicKey, ED25519};
+
+use crate::util::config::fns::get_signature_policy;
+use crate::util::config::structs::SignaturePolicy;
+use crate::util::macros::get_root;
+use crate::util::packaging::fns::read_package_entries;
+use crate::util::signing::structs::{SignatureError, TrustedKey};
+
+/// Returns the path of the trusted key directory
+pub fn get_keys_path() -> String {
+    get_root() + "/etc/bulge/keys"
+}
+
+/// Returns the fingerprint of a hex encoded public key, the hex encoded SHA-256 of the raw key
+pub fn get_fingerprint(public_key: &str) -> Result<String, hex::FromHexError> {
+    let raw_key = hex::decode(public_key.trim())?;
+
+    Ok(digest(&SHA256, &raw_key).as_ref().encode_hex::<String>())
+}
+
+/// Look for a key by its fingerprint, a unique prefix of the fingerprint is accepted
+pub fn find_key(fingerprint: &str) -> Option<TrustedKey> {
+    let fingerprint = fingerprint.to_lowercase();
+
+    let mut matches: Vec<TrustedKey> = load_keys().into_iter()
+        .filter(|k| get_fingerprint(&k.public_key).is_ok_and(|f| f.starts_with(&fingerprint)))
+        .collect();
+
+    if matches.len() != 1 {
+        return None;
+    }
+
+    matches.pop()
+}
+
+/// Write a key to the key directory, it's saved as `<fingerprint>.json` if it wasn't loaded from a file
+pub fn save_key(key: &TrustedKey) -> std::io::Result<()> {
+    let path = if key.path.as_os_str().is_empty() {
+        let fingerprint = get_fingerprint(&key.public_key)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+        Path::new(&get_keys_path()).join(format!("{}.json", fingerprint))
+    } else {
+        key.path.clone()
+    };
+
+    fs::create_dir_all(get_keys_path())?;
+    fs::write(path, serde_json::to_string_pretty(key)?)
+}
+
+/// Load every key in the key directory
+pub fn load_keys() -> Vec<TrustedKey> {
+    let mut keys: Vec<TrustedKey> = vec![];
+
+    let entries = match fs::read_dir(get_keys_path()) {
+        Ok(entries) => entries,
+        Err(_) => return keys,
+    };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+
+        if path.extension().is_none_or(|e| e != "json") {
+            continue;
+        }
+
+        let key: Result<TrustedKey, serde_json::Error> = File::open(&path)
+            .map_err(serde_json::Error::io)
+            .and_then(serde_json::from_reader);
+
+        match key {
+            Ok(mut key) => {
+                key.path = path;
+                keys.push(key);
+            },
+            Err(e) => println!("WARN> Failed to load key {}: {}", path.display(), e),
+        }
+    }
+
+    keys
+}
+
+/// Returns whether any trusted key may sign for a repo, either scoped to it or unscoped
+pub fn has_trusted_key(repo: &String) -> bool {
+    load_keys().iter().any(|k| k.trusted && k.repo.as_ref().is_none_or(|r| r == repo))
+}
+
+/// Sign a message with a PKCS#8 encoded ed25519 private key, returns the hex encoded detached signature
+pub fn sign_message(private_key: &Path, message: &[u8]) -> Result<String, SignatureError> {
+    let pkcs8 = fs::read(private_key).map_err(|_| SignatureError::Key)?;
+
+    let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(&pkcs8).map_err(|_| SignatureError::Key)?;
+
+    Ok(key_pair.sign(message).as_ref().encode_hex::<String>())
+}
+
+/// Check a detached hex encoded ed25519 signature against the trusted keys for a repo
+pub fn verify_signature(repo: &String, message: &[u8], signature: &[u8]) -> bool {
+    let signature = match hex::decode(String::from_utf8_lossy(signature).trim()) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+
+    for key in load_keys() {
+        if !key.trusted || key.repo.as_ref().is_some_and(|r| r != repo) {
+            continue;
+        }
+
+        let public_key = match hex::decode(key.public_key.trim()) {
+            Ok(public_key) => public_key,
+            Err(_) => continue,
+        };
+
+        if UnparsedPublicKey::new(&ED25519, public_key).verify(message, &signature).is_ok() {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Check a package against the signature policy of the repo it came from.
+///
+/// A detached signature covers the whole archive, an embedded `PKG.sig` covers `PKG` followed by `data.tar.xz`.
+/// Both are read straight from the archive so this can run before anything is unpacked.
+/// ed25519 can't verify a stream, so the signed bytes are held in memory once but never copied.
+pub fn verify_package(repo: &String, archive: &Path, detached: Option<&[u8]>) -> Result<(), SignatureError> {
+    let policy = get_signature_policy(repo);
+
+    if policy == SignaturePolicy::Never {
+        return Ok(());
+    }
+
+    if let Some(signature) = detached {
+        let contents = fs::read(archive).map_err(SignatureError::Io)?;
+
+        return match verify_signature(repo, &contents, signature) {
+            true => Ok(()),
+            false => Err(SignatureError::Invalid),
+        };
+    }
+
+    // PKG and data.tar.xz are read straight into the signed message, the archive itself is only streamed
+    let mut message: Vec<u8> = vec![];
+    let mut signature: Option<Vec<u8>> = None;
+
+    // Anything besides the signed entries is rejected, installs only ever extract these
+    read_package_entries(File::open(archive).map_err(SignatureError::Io)?, |name, entry| {
+        match name {
+            "PKG.sig" => {
+                let mut contents: Vec<u8> = vec![];
+                entry.read_to_end(&mut contents)?;
+                signature = Some(contents);
+            },
+            "PKG" => {
+                let pkg_len = entry.read_to_end(&mut message)?;
+
+                // PKG has to come first, move it in front of data.tar.xz if that was read already
+                message.rotate_right(pkg_len);
+            },
+            _ => {
+                entry.read_to_end(&mut message)?;
+            },
+        }
+
+        Ok(())
+    }).map_err(SignatureError::Io)?;
+
+    if let Some(signature) = signature {
+        return match verify_signature(repo, &message, &signature) {
+            true => Ok(()),
+            false => Err(SignatureError::Invalid),
+        };
+    }
+
+    match policy {
+        SignaturePolicy::Optional => Ok(()),
+        _ => Err(SignatureError::Missing),
+    }
+}
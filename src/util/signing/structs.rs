@@ -0,0 +1,49 @@
+use std::{error::Error, fmt};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Struct form of a trusted key file in /etc/bulge/keys.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TrustedKey {
+    /// Hex encoded ed25519 public key.
+    pub public_key: String,
+    /// Repo this key may sign for, any repo may use it if unset.
+    pub repo: Option<String>,
+    /// Untrusted keys are kept on disk but never used for verification.
+    #[serde(default = "default_trusted")]
+    pub trusted: bool,
+    /// File the key was loaded from.
+    #[serde(skip)]
+    pub path: PathBuf
+}
+
+fn default_trusted() -> bool {
+    true
+}
+
+/// Reasons a package can fail its signature check.
+#[derive(Debug)]
+pub enum SignatureError {
+    /// The repo requires a signature but the package has none.
+    Missing,
+    /// The package is signed but not by a trusted key.
+    Invalid,
+    /// A private key couldn't be read for signing.
+    Key,
+    /// The package couldn't be read.
+    Io(std::io::Error)
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignatureError::Missing => write!(f, "Package is not signed!"),
+            SignatureError::Invalid => write!(f, "Package signature is not from a trusted key!"),
+            SignatureError::Key => write!(f, "Signing key is not a valid PKCS#8 ed25519 key!"),
+            SignatureError::Io(e) => write!(f, "Package couldn't be read: {}", e),
+        }
+    }
+}
+
+impl Error for SignatureError {}
@@ -1,17 +1,10 @@
 use std::path::Path;
-use crate::util::database::fns::get_conflicts;
-use crate::util::database::structs::InstalledPackages;
 
 pub struct ConflictingFiles {
     pub is_conflict: bool,
     pub files: Vec<String>,
 }
 
-pub struct ConflictingPackages {
-    pub is_conflict: bool,
-    pub packages: Vec<InstalledPackages>,
-}
-
 pub fn run_conflict_check(files: &Vec<String>, is_installed: bool, root: String) -> ConflictingFiles {
     let mut conflicting_struct = ConflictingFiles {
         is_conflict: false,
@@ -27,23 +20,3 @@ pub fn run_conflict_check(files: &Vec<String>, is_installed: bool, root: String)
 
     return conflicting_struct;
 }
-
-pub fn run_conflict_package_check(package: &String) -> ConflictingPackages {
-    let mut conflicting_struct = ConflictingPackages {
-        is_conflict: false,
-        packages: vec![]
-    };
-
-    for i in get_conflicts(package) {
-        if &i.name == package {
-            // Whoops we found a conflict with ourselves, lets skip this one
-            // TODO: Find a better way to search in sqlite so this isn't needed
-            continue;
-        }
-
-        conflicting_struct.is_conflict = true;
-        conflicting_struct.packages.push(i);
-    }
-
-    return conflicting_struct;
-}
\ No newline at end of file
@@ -2,11 +2,11 @@ use std::fs;
 use std::fs::File;
 use std::path::Path;
 use version_compare::Version;
-use crate::util::database::fns::{add_package_to_installed, get_installed_package};
-use crate::util::database::structs::Source;
+use crate::util::database::fns::{add_package_to_installed, get_install_reason, get_installed_package};
+use crate::util::database::structs::{InstallReason, Source};
 use crate::util::lock::remove_lock;
 use crate::util::macros::{continue_prompt, get_root, string_to_vec};
-use crate::util::packaging::fns::{decode_pkg_file, decompress_xz};
+use crate::util::packaging::fns::{decode_pkg_file, decompress_xz, extract_package};
 use crate::util::packaging::structs::{NewPackage, Package};
 use crate::util::transactions::conflict::run_conflict_check;
 
@@ -14,12 +14,13 @@ use crate::util::transactions::conflict::run_conflict_check;
 pub struct InstallTransaction {
     pub package: Package,
     pub source: Source,
+    /// Recorded for new installs, packages that are already installed keep the reason they have.
+    pub reason: InstallReason,
 }
 
 pub fn run_install(install: InstallTransaction, file: File) {
-    let mut package_tar = decompress_xz(file);
-
-    package_tar.unpack(format!("{}/tmp/bulge/{}", get_root(), &install.package.name))
+    // Only the entries verify_package checked are extracted
+    extract_package(file, Path::new(&format!("{}/tmp/bulge/{}", get_root(), &install.package.name)))
         .expect("Failed to unpack package");
 
     let package = decode_pkg_file(fs::File::open(format!("{}/tmp/bulge/{}/PKG", get_root(), &install.package.name))
@@ -128,5 +129,6 @@ pub fn run_install(install: InstallTransaction, file: File) {
         provides: string_to_vec(install.package.provides),
         conflicts: string_to_vec(install.package.conflicts),
         dependencies: string_to_vec(install.package.depends),
+        reason: if reinstall { get_install_reason(&install.package.name) } else { install.reason },
     }, install.source);
 }
\ No newline at end of file
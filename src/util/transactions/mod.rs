@@ -1,3 +1,3 @@
 pub mod install;
 pub mod conflict;
-pub mod dependencies;
\ No newline at end of file
+pub mod resolver;
\ No newline at end of file
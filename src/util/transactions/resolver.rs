@@ -0,0 +1,724 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::{error::Error, fmt};
+
+use crate::util::config::fns::{get_default_provider, get_sources};
+use crate::util::database::fns::{find_package_repos, get_all_installed, get_provides, get_remote_package};
+use crate::util::database::structs::InstalledPackages;
+use crate::util::macros::number_prompt;
+use crate::util::packaging::structs::Package;
+use crate::util::version::{parse_depends, Constraint};
+
+/// Something the transaction has to satisfy, along with who asked for it.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Requirement {
+    pub constraint: Constraint,
+    /// Package that depends on this, None for packages the user asked for.
+    pub required_by: Option<String>,
+    /// Only look in this repo, set by repo/package.
+    pub repo: Option<String>
+}
+
+impl Requirement {
+    fn describe(&self) -> String {
+        match &self.required_by {
+            Some(package) => format!("{} requires {}", package, self.constraint),
+            None => format!("{} was requested", self.constraint),
+        }
+    }
+}
+
+/// A complete transaction.
+pub struct Plan {
+    /// Packages to install along with the repo they come from, dependencies first.
+    pub install: Vec<(Package, String)>,
+    /// Installed packages to remove, each with the package replacing it.
+    pub remove: Vec<(String, String)>,
+    /// Packages in `install` that were asked for rather than pulled in as a dependency.
+    pub explicit: Vec<String>
+}
+
+/// Why a transaction can't be satisfied, as the chain of reasons that led to the failure.
+#[derive(Debug)]
+pub struct ResolveError {
+    pub chain: Vec<String>
+}
+
+impl Error for ResolveError {}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.chain.join(", "))
+    }
+}
+
+/// A package picked for the transaction and the reason it was picked.
+#[derive(Clone)]
+struct Picked {
+    package: Package,
+    repo: String,
+    reason: String,
+    /// Picked for a requirement nobody depends on.
+    explicit: bool
+}
+
+#[derive(Clone)]
+struct State {
+    picked: Vec<Picked>,
+    remove: Vec<(String, String)>
+}
+
+/// Parse a comma separated list of names, entries that aren't valid constraints are skipped.
+fn parse_list(list: &str) -> Vec<Constraint> {
+    list.split(',')
+        .filter(|x| !x.trim().is_empty())
+        .filter_map(|x| Constraint::parse(x).ok())
+        .collect()
+}
+
+/// Returns true if a conflicts list matches a package, either by name and version or by something it provides.
+fn matches_conflicts(conflicts: &[Constraint], name: &str, version: &str, epoch: i32, provides: &[String]) -> bool {
+    conflicts.iter().any(|c| {
+        (c.name == name && c.satisfied_by(version, epoch)) || (c.operator.is_none() && provides.contains(&c.name))
+    })
+}
+
+fn provided_names(package: &Package) -> Vec<String> {
+    parse_list(&package.provides).into_iter().map(|c| c.name).collect()
+}
+
+fn installed_provided_names(package: &InstalledPackages) -> Vec<String> {
+    package.provides.iter().filter_map(|p| Constraint::parse(p).ok()).map(|c| c.name).collect()
+}
+
+/// Returns true if two repo packages conflict in either direction.
+fn packages_conflict(a: &Package, b: &Package) -> bool {
+    matches_conflicts(&parse_list(&a.conflicts), &b.name, &b.version, b.epoch, &provided_names(b))
+        || matches_conflicts(&parse_list(&b.conflicts), &a.name, &a.version, a.epoch, &provided_names(a))
+}
+
+/// Describe a conflict between two repo packages from the side that declares it.
+fn describe_conflict(package: &Package, other: &Package) -> String {
+    if matches_conflicts(&parse_list(&other.conflicts), &package.name, &package.version, package.epoch, &provided_names(package)) {
+        format!("{} conflicts with {}", other.name, package.name)
+    } else {
+        format!("{} conflicts with {}", package.name, other.name)
+    }
+}
+
+/// Returns true if a repo package and an installed package conflict in either direction.
+fn conflicts_with_installed(package: &Package, installed: &InstalledPackages) -> bool {
+    let installed_conflicts: Vec<Constraint> = installed.conflicts.iter().filter_map(|c| Constraint::parse(c).ok()).collect();
+
+    matches_conflicts(&parse_list(&package.conflicts), &installed.name, &installed.version, installed.epoch, &installed_provided_names(installed))
+        || matches_conflicts(&installed_conflicts, &package.name, &package.version, package.epoch, &provided_names(package))
+}
+
+/// Where the resolver looks up repo packages, results are in repo priority order.
+trait RepoLookup {
+    /// Every repo package with a name, from only `repo` if one is given.
+    fn packages(&self, name: &str, repo: Option<&String>) -> Vec<(Package, String)>;
+
+    /// Every repo package whose provides mention a name, from only `repo` if one is given. May match substrings.
+    fn providers(&self, name: &str, repo: Option<&String>) -> Vec<(Package, String)>;
+}
+
+/// The synced repo databases of every active source.
+struct SyncedRepos;
+
+impl RepoLookup for SyncedRepos {
+    fn packages(&self, name: &str, repo: Option<&String>) -> Vec<(Package, String)> {
+        let repos: Vec<String> = match repo {
+            Some(repo) => vec![repo.clone()],
+            None => find_package_repos(&name.to_string()),
+        };
+
+        repos.into_iter()
+            .filter_map(|repo| get_remote_package(&name.to_string(), &repo).ok().map(|p| (p, repo)))
+            .collect()
+    }
+
+    fn providers(&self, name: &str, repo: Option<&String>) -> Vec<(Package, String)> {
+        let repos: Vec<String> = match repo {
+            Some(repo) => vec![repo.clone()],
+            None => get_sources().into_iter().map(|s| s.name).collect(),
+        };
+
+        repos.into_iter()
+            .flat_map(|repo| get_provides(&repo, &name.to_string()).into_iter().map(move |p| (p, repo.clone())))
+            .collect()
+    }
+}
+
+struct Resolver<'a> {
+    installed: Vec<InstalledPackages>,
+    repos: &'a dyn RepoLookup,
+    /// Providers the user picked for virtual packages, by name.
+    choices: RefCell<HashMap<String, (String, String)>>
+}
+
+impl Resolver<'_> {
+    /// Returns true if something already installed and staying installed satisfies a requirement.
+    fn installed_satisfies(&self, state: &State, requirement: &Requirement) -> bool {
+        let constraint = &requirement.constraint;
+
+        self.installed.iter()
+            .filter(|i| !state.remove.iter().any(|(old, _)| old == &i.name))
+            .any(|i| {
+                (i.name == constraint.name && constraint.satisfied_by(&i.version, i.epoch))
+                    || (constraint.operator.is_none() && installed_provided_names(i).contains(&constraint.name))
+            })
+    }
+
+    /// Every repo package that could satisfy a requirement, most preferred first.
+    fn candidates(&self, requirement: &Requirement) -> Result<Vec<(Package, String)>, ResolveError> {
+        let constraint = &requirement.constraint;
+
+        let mut candidates: Vec<(Package, String)> = vec![];
+        let mut rejected: Option<(Package, String)> = None;
+
+        for (package, repo) in self.repos.packages(&constraint.name, requirement.repo.as_ref()) {
+            if constraint.satisfied_by(&package.version, package.epoch) {
+                candidates.push((package, repo));
+            } else {
+                rejected.get_or_insert((package, repo));
+            }
+        }
+
+        // Virtual packages are satisfied by anything that provides them
+        if candidates.is_empty() && rejected.is_none() && constraint.operator.is_none() {
+            candidates = self.choose_provider(&constraint.name, self.providers(requirement));
+        }
+
+        if candidates.is_empty() {
+            let reason = match rejected {
+                Some((package, repo)) => format!("but {} only has {} {}-{}", repo, package.name, package.version, package.epoch),
+                None => format!("but no repo has {}", constraint.name),
+            };
+
+            return Err(ResolveError { chain: vec![requirement.describe(), reason] });
+        }
+
+        Ok(candidates)
+    }
+
+    /// Every repo package that provides a name, in repo priority order.
+    fn providers(&self, requirement: &Requirement) -> Vec<(Package, String)> {
+        self.repos.providers(&requirement.constraint.name, requirement.repo.as_ref()).into_iter()
+            // Lookups can match substrings, so check the name properly
+            .filter(|(package, _)| provided_names(package).contains(&requirement.constraint.name))
+            .collect()
+    }
+
+    /// Narrow the providers of a virtual package down to one.
+    ///
+    /// An installed provider is kept without asking, otherwise the user picks one with the configured default
+    /// provider (or the first one) used for --noconfirm. The pick is remembered for the rest of the transaction.
+    fn choose_provider(&self, name: &String, providers: Vec<(Package, String)>) -> Vec<(Package, String)> {
+        let mut names: Vec<String> = providers.iter().map(|(p, _)| p.name.clone()).collect();
+        names.sort();
+        names.dedup();
+
+        if names.len() <= 1 {
+            return providers;
+        }
+
+        if let Some(installed) = names.iter().find(|n| self.installed.iter().any(|i| &i.name == *n)) {
+            return providers.into_iter().filter(|(p, _)| &p.name == installed).collect();
+        }
+
+        if let Some((package, repo)) = self.choices.borrow().get(name) {
+            return providers.into_iter().filter(|(p, r)| &p.name == package && r == repo).collect();
+        }
+
+        let default = get_default_provider(name)
+            .and_then(|d| providers.iter().position(|(p, _)| p.name == d))
+            .unwrap_or(0);
+
+        println!("==> There are {} providers available for {}:", providers.len(), name);
+
+        let options: Vec<String> = providers.iter()
+            .map(|(p, r)| format!("{}/{} {}-{}", r, p.name, p.version, p.epoch))
+            .collect();
+
+        let (package, repo) = providers[number_prompt(&options, default)].clone();
+
+        self.choices.borrow_mut().insert(name.clone(), (package.name.clone(), repo.clone()));
+
+        vec![(package, repo)]
+    }
+
+    /// Add a package to the transaction, failing if it conflicts with anything picked or installed.
+    fn pick(&self, state: &State, package: Package, repo: String, requirement: &Requirement) -> Result<State, ResolveError> {
+        for i in &state.picked {
+            if packages_conflict(&package, &i.package) {
+                return Err(ResolveError { chain: vec![
+                    requirement.describe(),
+                    i.reason.clone(),
+                    describe_conflict(&package, &i.package)
+                ]});
+            }
+        }
+
+        let mut state = state.clone();
+
+        for i in &self.installed {
+            // Installing the same name is an upgrade or reinstall, not a conflict
+            if i.name == package.name || state.remove.iter().any(|(old, _)| old == &i.name) {
+                continue;
+            }
+
+            if !conflicts_with_installed(&package, i) {
+                continue;
+            }
+
+            // A package that provides what it conflicts with can take its place
+            if provided_names(&package).contains(&i.name) {
+                state.remove.push((i.name.clone(), package.name.clone()));
+                continue;
+            }
+
+            return Err(ResolveError { chain: vec![
+                requirement.describe(),
+                format!("{} conflicts with installed {} {}-{}", package.name, i.name, i.version, i.epoch)
+            ]});
+        }
+
+        state.picked.push(Picked { package, repo, reason: requirement.describe(), explicit: requirement.required_by.is_none() });
+
+        self.check_installed_depends(&state)?;
+
+        Ok(state)
+    }
+
+    /// Make sure installed packages that stay installed still have their depends satisfied.
+    ///
+    /// Only depends that were satisfied before the transaction are checked, so an already broken system doesn't block it.
+    fn check_installed_depends(&self, state: &State) -> Result<(), ResolveError> {
+        let replaced = |name: &String| {
+            state.remove.iter().any(|(old, _)| old == name) || state.picked.iter().any(|p| &p.package.name == name)
+        };
+
+        // Only names the transaction touches can break anything
+        let mut touched: Vec<String> = vec![];
+
+        for i in self.installed.iter().filter(|i| replaced(&i.name)) {
+            touched.push(i.name.clone());
+            touched.extend(installed_provided_names(i));
+        }
+
+        if touched.is_empty() {
+            return Ok(());
+        }
+
+        let installed_match = |i: &InstalledPackages, c: &Constraint| {
+            (i.name == c.name && c.satisfied_by(&i.version, i.epoch))
+                || (c.operator.is_none() && installed_provided_names(i).contains(&c.name))
+        };
+
+        let picked_match = |p: &Picked, c: &Constraint| {
+            (p.package.name == c.name && c.satisfied_by(&p.package.version, p.package.epoch))
+                || (c.operator.is_none() && provided_names(&p.package).contains(&c.name))
+        };
+
+        for i in self.installed.iter().filter(|i| !replaced(&i.name)) {
+            for constraint in i.dependencies.iter().filter_map(|d| Constraint::parse(d).ok()) {
+                if !touched.contains(&constraint.name) || !self.installed.iter().any(|x| installed_match(x, &constraint)) {
+                    continue;
+                }
+
+                let still_satisfied = self.installed.iter().filter(|x| !replaced(&x.name)).any(|x| installed_match(x, &constraint))
+                    || state.picked.iter().any(|p| picked_match(p, &constraint));
+
+                if still_satisfied {
+                    continue;
+                }
+
+                let reason = match state.picked.iter().find(|p| p.package.name == constraint.name) {
+                    Some(p) => format!("but {} {}-{} was picked because {}", p.package.name, p.package.version, p.package.epoch, p.reason),
+                    None => match state.remove.iter().find(|(old, _)| old == &constraint.name) {
+                        Some((old, new)) => format!("but {} is being replaced by {}", old, new),
+                        None => format!("but nothing left installed provides {}", constraint.name),
+                    },
+                };
+
+                return Err(ResolveError { chain: vec![format!("installed {} requires {}", i.name, constraint), reason] });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Work through pending requirements, backtracking over candidates until everything is satisfied.
+    fn solve(&self, state: State, mut pending: VecDeque<Requirement>) -> Result<State, ResolveError> {
+        while let Some(requirement) = pending.pop_front() {
+            let constraint = &requirement.constraint;
+
+            if let Some(picked) = state.picked.iter().find(|p| p.package.name == constraint.name) {
+                if constraint.satisfied_by(&picked.package.version, picked.package.epoch) {
+                    continue;
+                }
+
+                return Err(ResolveError { chain: vec![
+                    requirement.describe(),
+                    format!("but {} {}-{} was picked because {}", picked.package.name, picked.package.version, picked.package.epoch, picked.reason)
+                ]});
+            }
+
+            if constraint.operator.is_none() && state.picked.iter().any(|p| provided_names(&p.package).contains(&constraint.name)) {
+                continue;
+            }
+
+            // Packages the user asked for are always (re)installed
+            if requirement.required_by.is_some() && self.installed_satisfies(&state, &requirement) {
+                continue;
+            }
+
+            let mut error: Option<ResolveError> = None;
+
+            for (package, repo) in self.candidates(&requirement)? {
+                let depends = match parse_depends(&package.depends) {
+                    Ok(depends) => depends,
+                    Err(e) => {
+                        error.get_or_insert(ResolveError { chain: vec![requirement.describe(), format!("{} has a broken depends list: {}", package.name, e)] });
+                        continue;
+                    }
+                };
+
+                let name = package.name.clone();
+
+                let next = match self.pick(&state, package, repo, &requirement) {
+                    Ok(next) => next,
+                    Err(e) => {
+                        error.get_or_insert(e);
+                        continue;
+                    }
+                };
+
+                let mut next_pending = pending.clone();
+                next_pending.extend(depends.into_iter().map(|constraint| Requirement {
+                    constraint,
+                    required_by: Some(name.clone()),
+                    repo: None
+                }));
+
+                match self.solve(next, next_pending) {
+                    Ok(solved) => return Ok(solved),
+                    Err(e) => { error.get_or_insert(e); },
+                }
+            }
+
+            // Report the failure of the most preferred candidate
+            return Err(error.expect("Candidates can't be empty"));
+        }
+
+        Ok(state)
+    }
+}
+
+/// Sort picked packages so everything comes after the packages it depends on.
+///
+/// Dependencies satisfied by installed packages don't affect the order. A cycle is broken at the depend that closes
+/// it and returned as "a -> b -> a", so the rest of the order is still right.
+fn sort_by_depends(picked: Vec<Picked>) -> (Vec<Picked>, Vec<String>) {
+    // Edges point from a package to the picked packages satisfying its depends
+    let edges: Vec<Vec<usize>> = picked.iter().map(|p| {
+        parse_depends(&p.package.depends).unwrap_or_default().iter()
+            .filter_map(|c| {
+                picked.iter().position(|q| q.package.name == c.name)
+                    .or_else(|| picked.iter().position(|q| c.operator.is_none() && provided_names(&q.package).contains(&c.name)))
+            })
+            .collect()
+    }).collect();
+
+    // Marks are 0 for unvisited, 1 while on the current path and 2 once sorted
+    fn visit(i: usize, edges: &[Vec<usize>], picked: &[Picked], marks: &mut Vec<u8>, path: &mut Vec<usize>, order: &mut Vec<usize>, cycles: &mut Vec<String>) {
+        match marks[i] {
+            2 => return,
+            1 => {
+                let start = path.iter().position(|x| *x == i).expect("Cycle start not on path");
+
+                let mut cycle: Vec<String> = path[start..].iter().map(|x| picked[*x].package.name.clone()).collect();
+                cycle.push(picked[i].package.name.clone());
+
+                // Skipping the back edge breaks the cycle
+                cycles.push(cycle.join(" -> "));
+                return;
+            },
+            _ => {},
+        }
+
+        marks[i] = 1;
+        path.push(i);
+
+        for x in &edges[i] {
+            if *x != i {
+                visit(*x, edges, picked, marks, path, order, cycles);
+            }
+        }
+
+        path.pop();
+        marks[i] = 2;
+        order.push(i);
+    }
+
+    let mut marks: Vec<u8> = vec![0; picked.len()];
+    let mut path: Vec<usize> = vec![];
+    let mut order: Vec<usize> = vec![];
+    let mut cycles: Vec<String> = vec![];
+
+    for i in 0..picked.len() {
+        visit(i, &edges, &picked, &mut marks, &mut path, &mut order, &mut cycles);
+    }
+
+    let mut picked: Vec<Option<Picked>> = picked.into_iter().map(Some).collect();
+
+    (order.into_iter().map(|i| picked[i].take().expect("Package sorted twice")).collect(), cycles)
+}
+
+/// Resolve the requested packages and everything they depend on into a complete transaction.
+///
+/// Installed packages satisfy dependencies, provides are used for virtual packages and conflicting installed
+/// packages are only removed when a new package provides them. Installed packages that stay keep their depends
+/// satisfied. Packages to install are sorted by their depends.
+///
+/// `replacing` lists installed packages that are removed in favour of a requested one, like upgrade does for replaces.
+pub fn resolve(requested: Vec<Requirement>, replacing: Vec<(String, String)>) -> Result<Plan, ResolveError> {
+    let resolver = Resolver {
+        installed: get_all_installed(),
+        repos: &SyncedRepos,
+        choices: RefCell::new(HashMap::new())
+    };
+
+    let state = State {
+        picked: vec![],
+        remove: replacing
+    };
+
+    let state = resolver.solve(state, requested.into_iter().collect())?;
+
+    let (install, cycles) = sort_by_depends(state.picked);
+
+    for cycle in cycles {
+        println!("WARN> Dependency cycle detected: {}, installing it in an arbitrary order.", cycle);
+    }
+
+    Ok(Plan {
+        explicit: install.iter().filter(|p| p.explicit).map(|p| p.package.name.clone()).collect(),
+        install: install.into_iter().map(|p| (p.package, p.repo)).collect(),
+        remove: state.remove
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, version: &str, depends: &str, provides: &str, conflicts: &str) -> Package {
+        Package {
+            name: name.to_string(),
+            version: version.to_string(),
+            epoch: 0,
+            description: String::new(),
+            groups: String::new(),
+            url: String::new(),
+            license: String::new(),
+            depends: depends.to_string(),
+            optional_depends: String::new(),
+            provides: provides.to_string(),
+            conflicts: conflicts.to_string(),
+            replaces: String::new(),
+            sha512sum: String::new()
+        }
+    }
+
+    fn installed(name: &str, version: &str, depends: &[&str], conflicts: &[&str]) -> InstalledPackages {
+        InstalledPackages {
+            name: name.to_string(),
+            groups: vec![],
+            source: "core".to_string(),
+            version: version.to_string(),
+            epoch: 0,
+            installed_files: vec![],
+            provides: vec![name.to_string()],
+            conflicts: conflicts.iter().map(|c| c.to_string()).collect(),
+            dependencies: depends.iter().map(|d| d.to_string()).collect()
+        }
+    }
+
+    /// Repo packages in priority order, along with the repo each is in
+    struct FakeRepos(Vec<(Package, &'static str)>);
+
+    impl RepoLookup for FakeRepos {
+        fn packages(&self, name: &str, repo: Option<&String>) -> Vec<(Package, String)> {
+            self.0.iter()
+                .filter(|(p, r)| p.name == name && repo.is_none_or(|repo| repo == r))
+                .map(|(p, r)| (p.clone(), r.to_string()))
+                .collect()
+        }
+
+        fn providers(&self, name: &str, repo: Option<&String>) -> Vec<(Package, String)> {
+            self.0.iter()
+                .filter(|(p, r)| p.provides.contains(name) && repo.is_none_or(|repo| repo == r))
+                .map(|(p, r)| (p.clone(), r.to_string()))
+                .collect()
+        }
+    }
+
+    fn solve(repos: &FakeRepos, installed: Vec<InstalledPackages>, requested: &[&str]) -> Result<State, ResolveError> {
+        let resolver = Resolver {
+            installed,
+            repos,
+            choices: RefCell::new(HashMap::new())
+        };
+
+        let requested: VecDeque<Requirement> = requested.iter().map(|r| Requirement {
+            constraint: Constraint::parse(r).unwrap(),
+            required_by: None,
+            repo: None
+        }).collect();
+
+        resolver.solve(State { picked: vec![], remove: vec![] }, requested)
+    }
+
+    fn picked_versions(state: &State) -> Vec<String> {
+        state.picked.iter().map(|p| format!("{}/{}-{}", p.repo, p.package.name, p.package.version)).collect()
+    }
+
+    fn picked(name: &str, depends: &str, provides: &str) -> Picked {
+        Picked {
+            package: package(name, "1.0", depends, provides, ""),
+            repo: "core".to_string(),
+            reason: format!("{} was requested", name),
+            explicit: true
+        }
+    }
+
+    fn names(sorted: &[Picked]) -> Vec<&str> {
+        sorted.iter().map(|p| p.package.name.as_str()).collect()
+    }
+
+    #[test]
+    fn sorts_dependencies_first() {
+        let (sorted, cycles) = sort_by_depends(vec![
+            picked("app", "lib>=1.0,sh", ""),
+            picked("lib", "libc", ""),
+            picked("libc", "", ""),
+            picked("bash", "libc", "sh")
+        ]);
+
+        assert_eq!(names(&sorted), ["libc", "lib", "bash", "app"]);
+        assert!(cycles.is_empty());
+    }
+
+    #[test]
+    fn ignores_depends_outside_the_transaction() {
+        let (sorted, cycles) = sort_by_depends(vec![picked("app", "installed", ""), picked("lib", "", "")]);
+
+        assert_eq!(names(&sorted), ["app", "lib"]);
+        assert!(cycles.is_empty());
+    }
+
+    #[test]
+    fn reports_and_breaks_cycles() {
+        let (sorted, cycles) = sort_by_depends(vec![
+            picked("a", "b", ""),
+            picked("b", "c", ""),
+            picked("c", "a,libc", ""),
+            picked("libc", "", ""),
+            picked("app", "a", "")
+        ]);
+
+        assert_eq!(cycles, ["a -> b -> c -> a"]);
+        // Everything is still sorted once, with the rest of the order intact
+        assert_eq!(names(&sorted), ["libc", "c", "b", "a", "app"]);
+    }
+
+    #[test]
+    fn self_depends_are_not_cycles() {
+        let (sorted, cycles) = sort_by_depends(vec![picked("a", "a", "")]);
+
+        assert_eq!(names(&sorted), ["a"]);
+        assert!(cycles.is_empty());
+    }
+
+    #[test]
+    fn satisfies_depends_from_repos_and_installed() {
+        let repos = FakeRepos(vec![
+            (package("app", "1.0", "lib>=2,libc", "", ""), "core"),
+            (package("lib", "2.1", "", "", ""), "core"),
+            (package("libc", "2.0", "", "", ""), "core")
+        ]);
+
+        let state = solve(&repos, vec![installed("libc", "1.0", &[], &[])], &["app"]).unwrap();
+
+        assert_eq!(picked_versions(&state), ["core/app-1.0", "core/lib-2.1"]);
+    }
+
+    #[test]
+    fn skips_candidates_that_conflict() {
+        // The preferred lib conflicts with something already picked, so the next repo's lib is used
+        let repos = FakeRepos(vec![
+            (package("tool", "1.0", "", "", ""), "core"),
+            (package("app", "1.0", "lib", "", ""), "core"),
+            (package("lib", "2.0", "", "", "tool"), "core"),
+            (package("lib", "1.0", "", "", ""), "extra")
+        ]);
+
+        let state = solve(&repos, vec![], &["tool", "app"]).unwrap();
+
+        assert_eq!(picked_versions(&state), ["core/tool-1.0", "core/app-1.0", "extra/lib-1.0"]);
+    }
+
+    #[test]
+    fn backtracks_over_a_failed_dependency() {
+        // app 2.0 needs newlib, which conflicts with installed oldlib, so app 1.0 is used instead
+        let repos = FakeRepos(vec![
+            (package("app", "2.0", "newlib", "", ""), "core"),
+            (package("newlib", "1.0", "", "", "oldlib"), "core"),
+            (package("app", "1.0", "oldlib", "", ""), "extra")
+        ]);
+
+        let state = solve(&repos, vec![installed("oldlib", "1.0", &[], &[])], &["app"]).unwrap();
+
+        assert_eq!(picked_versions(&state), ["extra/app-1.0"]);
+    }
+
+    #[test]
+    fn explains_conflicts() {
+        let repos = FakeRepos(vec![
+            (package("a", "1.0", "b>=2", "", ""), "core"),
+            (package("b", "2.0", "", "", ""), "core"),
+            (package("c", "1.0", "", "", "b"), "core")
+        ]);
+
+        let error = solve(&repos, vec![], &["c", "a"]).err().unwrap();
+
+        assert_eq!(error.to_string(), "a requires b>=2, c was requested, c conflicts with b");
+    }
+
+    #[test]
+    fn explains_missing_versions() {
+        let repos = FakeRepos(vec![
+            (package("a", "1.0", "b>=2", "", ""), "core"),
+            (package("b", "1.5", "", "", ""), "core")
+        ]);
+
+        let error = solve(&repos, vec![], &["a"]).err().unwrap();
+
+        assert_eq!(error.to_string(), "a requires b>=2, but core only has b 1.5-0");
+    }
+
+    #[test]
+    fn keeps_installed_depends_satisfied() {
+        // newlib provides lib but not a version of it, so installed app would lose lib>=1
+        let repos = FakeRepos(vec![(package("newlib", "1.0", "", "lib", "lib"), "core")]);
+
+        let error = solve(&repos, vec![
+            installed("app", "1.0", &["lib>=1"], &[]),
+            installed("lib", "1.0", &[], &[])
+        ], &["newlib"]).err().unwrap();
+
+        assert_eq!(error.to_string(), "installed app requires lib>=1, but lib is being replaced by newlib");
+    }
+}
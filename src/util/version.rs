@@ -0,0 +1,241 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use version_compare::Version;
+
+/// Compare two package versions, a higher epoch always wins before the versions themselves are compared.
+///
+/// These are the rules upgrade uses to decide if a repo package is newer.
+pub fn compare_versions(version: &str, epoch: i32, other_version: &str, other_epoch: i32) -> Ordering {
+    epoch.cmp(&other_epoch).then_with(|| {
+        Version::from(version).partial_cmp(&Version::from(other_version)).unwrap_or(Ordering::Equal)
+    })
+}
+
+/// Operators a dependency constraint can use.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operator {
+    Less,
+    LessEqual,
+    Equal,
+    GreaterEqual,
+    Greater
+}
+
+impl Operator {
+    fn sign(&self) -> &'static str {
+        match self {
+            Operator::Less => "<",
+            Operator::LessEqual => "<=",
+            Operator::Equal => "=",
+            Operator::GreaterEqual => ">=",
+            Operator::Greater => ">",
+        }
+    }
+}
+
+/// A dependency like `foo`, `foo>=1.2`, `bar<3` or `baz=2.0-1`.
+///
+/// A numeric part after the last "-" of the version is the epoch, constraints without one ignore the epoch.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Constraint {
+    pub name: String,
+    pub operator: Option<Operator>,
+    pub version: String,
+    pub epoch: Option<i32>
+}
+
+impl Constraint {
+    /// Parse a single entry of a depends list.
+    pub fn parse(depend: &str) -> Result<Constraint, String> {
+        let depend = depend.trim();
+
+        let start = match depend.find(['<', '>', '=']) {
+            Some(start) => start,
+            None => return Ok(Constraint {
+                name: depend.to_string(),
+                operator: None,
+                version: String::new(),
+                epoch: None
+            }),
+        };
+
+        let (name, rest) = depend.split_at(start);
+        let name = name.trim_end();
+
+        let (operator, rest) = if let Some(rest) = rest.strip_prefix(">=") {
+            (Operator::GreaterEqual, rest)
+        } else if let Some(rest) = rest.strip_prefix("<=") {
+            (Operator::LessEqual, rest)
+        } else if let Some(rest) = rest.strip_prefix('>') {
+            (Operator::Greater, rest)
+        } else if let Some(rest) = rest.strip_prefix('<') {
+            (Operator::Less, rest)
+        } else {
+            (Operator::Equal, rest.trim_start_matches('='))
+        };
+
+        let rest = rest.trim_start();
+
+        // Versions can contain "-" themselves, so only a numeric suffix is taken as the epoch
+        let (version, epoch) = match rest.rsplit_once('-') {
+            Some((version, epoch)) if epoch.parse::<i32>().is_ok() => (version, epoch.parse::<i32>().ok()),
+            _ => (rest, None),
+        };
+
+        if name.is_empty() || version.is_empty() || version.contains(['<', '>', '=']) || Version::from(version).is_none() {
+            return Err(format!("Invalid dependency {}", depend));
+        }
+
+        Ok(Constraint {
+            name: name.to_string(),
+            operator: Some(operator),
+            version: version.to_string(),
+            epoch
+        })
+    }
+
+    /// Returns true if a package with this version and epoch satisfies the constraint.
+    pub fn satisfied_by(&self, version: &str, epoch: i32) -> bool {
+        let operator = match self.operator {
+            Some(operator) => operator,
+            None => return true,
+        };
+
+        let ordering = match self.epoch {
+            Some(wanted_epoch) => compare_versions(version, epoch, &self.version, wanted_epoch),
+            None => compare_versions(version, 0, &self.version, 0),
+        };
+
+        match operator {
+            Operator::Less => ordering == Ordering::Less,
+            Operator::LessEqual => ordering != Ordering::Greater,
+            Operator::Equal => ordering == Ordering::Equal,
+            Operator::GreaterEqual => ordering != Ordering::Less,
+            Operator::Greater => ordering == Ordering::Greater,
+        }
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.operator, self.epoch) {
+            (None, _) => write!(f, "{}", self.name),
+            (Some(operator), None) => write!(f, "{}{}{}", self.name, operator.sign(), self.version),
+            (Some(operator), Some(epoch)) => write!(f, "{}{}{}-{}", self.name, operator.sign(), self.version, epoch),
+        }
+    }
+}
+
+/// Parse a comma separated depends list, empty entries are skipped.
+pub fn parse_depends(depends: &str) -> Result<Vec<Constraint>, String> {
+    depends.split(',')
+        .filter(|d| !d.trim().is_empty())
+        .map(Constraint::parse)
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_plain_names() {
+        let constraint = Constraint::parse(" foo ").unwrap();
+
+        assert_eq!(constraint.name, "foo");
+        assert!(constraint.operator.is_none());
+        assert!(constraint.satisfied_by("0.1", 0));
+    }
+
+    #[test]
+    fn parses_operators() {
+        let cases = [
+            ("foo>=1.2", Operator::GreaterEqual),
+            ("foo<=1.2", Operator::LessEqual),
+            ("foo>1.2", Operator::Greater),
+            ("foo<1.2", Operator::Less),
+            ("foo=1.2", Operator::Equal),
+            ("foo==1.2", Operator::Equal),
+            ("foo >= 1.2", Operator::GreaterEqual)
+        ];
+
+        for (depend, operator) in cases {
+            let constraint = Constraint::parse(depend).unwrap();
+
+            assert_eq!(constraint.name, "foo", "{}", depend);
+            assert!(constraint.operator == Some(operator), "{}", depend);
+            assert_eq!(constraint.version, "1.2", "{}", depend);
+            assert_eq!(constraint.epoch, None, "{}", depend);
+        }
+    }
+
+    #[test]
+    fn checks_versions() {
+        let constraint = Constraint::parse("foo>=1.2").unwrap();
+
+        assert!(constraint.satisfied_by("1.2", 0));
+        assert!(constraint.satisfied_by("1.10", 0));
+        assert!(!constraint.satisfied_by("1.1", 0));
+
+        let constraint = Constraint::parse("bar<3").unwrap();
+
+        assert!(constraint.satisfied_by("2.9", 0));
+        assert!(!constraint.satisfied_by("3", 0));
+        // Without an epoch in the constraint only the version counts
+        assert!(constraint.satisfied_by("2.9", 5));
+    }
+
+    #[test]
+    fn parses_epochs() {
+        let constraint = Constraint::parse("baz=2.0-1").unwrap();
+
+        assert_eq!(constraint.version, "2.0");
+        assert_eq!(constraint.epoch, Some(1));
+        assert!(constraint.satisfied_by("2.0", 1));
+        assert!(!constraint.satisfied_by("2.0", 0));
+
+        // A higher epoch wins over the version
+        assert!(Constraint::parse("baz>2.0-1").unwrap().satisfied_by("1.0", 2));
+    }
+
+    #[test]
+    fn keeps_non_numeric_suffixes_in_the_version() {
+        let constraint = Constraint::parse("foo>=1.0.0-dev").unwrap();
+
+        assert_eq!(constraint.version, "1.0.0-dev");
+        assert_eq!(constraint.epoch, None);
+    }
+
+    #[test]
+    fn rejects_malformed_constraints() {
+        for depend in [">=1.2", "foo>=", "foo<>1", "foo=>1", "foo>=abc", "foo="] {
+            assert!(Constraint::parse(depend).is_err(), "{}", depend);
+        }
+    }
+
+    #[test]
+    fn displays_constraints() {
+        for depend in ["foo", "foo>=1.2", "bar<3", "baz=2.0-1"] {
+            assert_eq!(Constraint::parse(depend).unwrap().to_string(), depend);
+        }
+    }
+
+    #[test]
+    fn parses_depends_lists() {
+        let depends = parse_depends("foo>=1.2, bar<3,,baz").unwrap();
+        let names: Vec<&str> = depends.iter().map(|c| c.name.as_str()).collect();
+
+        assert_eq!(names, ["foo", "bar", "baz"]);
+        assert!(parse_depends("").unwrap().is_empty());
+        assert!(parse_depends("foo, >=1").is_err());
+    }
+
+    #[test]
+    fn compares_epochs_first() {
+        assert_eq!(compare_versions("1.0", 1, "2.0", 0), Ordering::Greater);
+        assert_eq!(compare_versions("1.0", 0, "2.0", 0), Ordering::Less);
+        assert_eq!(compare_versions("2.0", 0, "2.0", 0), Ordering::Equal);
+    }
+}